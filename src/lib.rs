@@ -40,9 +40,15 @@ use std::{
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
 };
 
+#[cfg(target_os = "linux")]
+use std::time::Duration;
+
 mod platform_impl;
 use platform_impl::PlatformHandle;
 
+mod transaction;
+pub use transaction::RouteTransaction;
+
 #[cfg(all(target_os = "macos", not(doc)))]
 pub use platform_impl::ifname_to_index;
 
@@ -59,25 +65,217 @@ impl Handle {
         self.0.add(route).await
     }
 
+    /// Add many routes, keeping up to `window` requests in flight at once instead of waiting for
+    /// each route to be added before sending the next one.
+    ///
+    /// Returns the result of each route, in the order of `routes`. A failing route doesn't
+    /// prevent the others from being added.
+    #[cfg(target_os = "linux")]
+    pub async fn add_many(&self, routes: &[Route], window: usize) -> Vec<io::Result<()>> {
+        self.0.add_many(routes, window).await
+    }
+
+    /// Remove many routes like [`Handle::delete`], keeping up to `window` requests in flight at
+    /// once.
+    ///
+    /// Returns the result of each route, in the order of `routes`. Multipath routes are looked
+    /// up in a single dump of the routing table made before removing any route.
+    #[cfg(target_os = "linux")]
+    pub async fn delete_many(&self, routes: &[Route], window: usize) -> Vec<io::Result<()>> {
+        self.0.delete_many(routes, window).await
+    }
+
+    /// Add a route to the system's routing table after the existing routes with the same key.
+    ///
+    /// For IPv6, this adds the next hops of `route` to an existing route with the same key,
+    /// building a multipath route like `ip -6 route append`. Deleting the route with one of its
+    /// gateways set removes only that next hop.
+    #[cfg(target_os = "linux")]
+    pub async fn append(&self, route: &Route) -> io::Result<()> {
+        self.0.append(route).await
+    }
+
+    /// Add a route to the system's routing table, or atomically replace the existing route with
+    /// the same key.
+    ///
+    /// Unlike deleting and adding the route, this never leaves a window without the route.
+    ///
+    /// On Windows, the gateway is part of the key. A route with a different gateway is replaced
+    /// by adding `route` first, then removing the routes with the same destination, prefix and
+    /// interface.
+    pub async fn replace(&self, route: &Route) -> io::Result<()> {
+        self.0.replace(route).await
+    }
+
     /// Returns a `Stream` which will yield a `RouteChange` event whenever a route is added, removed, or changed from the system's routing table.
     pub fn route_listen_stream(&self) -> impl futures::Stream<Item = RouteChange> {
         self.0.route_listen_stream()
     }
 
+    /// Returns a `Stream` like [`Handle::route_listen_stream`], which only yields the events of
+    /// routes matching `filter`.
+    #[cfg(target_os = "linux")]
+    pub fn route_listen_stream_filtered(
+        &self,
+        filter: RouteFilter,
+    ) -> impl futures::Stream<Item = RouteChange> {
+        use futures::StreamExt;
+
+        self.0.route_listen_stream().filter(move |event| {
+            let route = match event {
+                RouteChange::Add(route)
+                | RouteChange::Delete(route)
+                | RouteChange::Change(route) => route,
+            };
+            futures::future::ready(filter.matches(route))
+        })
+    }
+
     /// Returns a `Vec<Route>` containing a list of both ipv4 and v6 routes on the system.
     pub async fn list(&self) -> io::Result<Vec<Route>> {
         self.0.list().await
     }
 
+    /// Ask the kernel which route it would use for traffic to `destination`, like `ip route get`.
+    ///
+    /// The returned route is the resolved one, including the preferred source address in
+    /// [`Route::source_hint`] and the cached path MTU in [`RouteMetrics::mtu`].
+    #[cfg(target_os = "linux")]
+    pub async fn get_route(&self, destination: IpAddr, options: &RouteLookup) -> io::Result<Route> {
+        self.0.get_route(destination, options).await
+    }
+
+    /// Returns a `Stream` yielding the ipv4 and v6 routes on the system while they are dumped
+    /// by the kernel, instead of collecting them first like [`Handle::list`].
+    ///
+    /// The routes are dumped when the stream is first polled. The next hops of an IPv6
+    /// multipath route are only merged into one route when the kernel dumps them next to each
+    /// other. Next hops added later with [`Handle::append`] can be dumped apart, and are then
+    /// yielded as separate routes, which [`Handle::list`] merges.
+    #[cfg(target_os = "linux")]
+    pub fn list_stream(&self) -> impl futures::Stream<Item = io::Result<Route>> {
+        self.0.list_stream()
+    }
+
+    /// Returns a `Vec<Route>` containing the routes matching `filter`.
+    ///
+    /// The kernel filters the routes by table, protocol, type and interface itself when it
+    /// supports strict checking of dump requests (Linux 4.20), which avoids transferring the
+    /// whole routing table.
+    #[cfg(target_os = "linux")]
+    pub async fn list_filtered(&self, filter: &RouteFilter) -> io::Result<Vec<Route>> {
+        self.0.list_filtered(filter).await
+    }
+
     /// Get one of the default routes on the system if there is at least one.
     pub async fn default_route(&self) -> io::Result<Option<Route>> {
         self.0.default_route().await
     }
 
     /// Remove a route from the system's routing table.
+    ///
+    /// On Linux, the route to remove is the one matching the key of `route`: its destination,
+    /// prefix, table, TOS and source prefix. Its metric, gateway and interface narrow the match
+    /// down when set, and match any route when `None`.
     pub async fn delete(&self, route: &Route) -> io::Result<()> {
         self.0.delete(route).await
     }
+
+    /// Remove every route matching `filter`.
+    ///
+    /// Returns each matching route along with the result of removing it, the removed routes
+    /// first. A failure doesn't stop the routes after it from being removed. Multipath routes are
+    /// merged as [`Handle::list`] reports them, with the next hops which failed to be removed
+    /// split off into a route of their own.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `filter` doesn't narrow down the routes
+    /// beyond their IP version, as it would remove the local and broadcast routes of the host
+    /// too.
+    #[cfg(target_os = "linux")]
+    pub async fn delete_matching(
+        &self,
+        filter: &RouteFilter,
+    ) -> io::Result<Vec<(Route, io::Result<()>)>> {
+        self.0.delete_matching(filter).await
+    }
+
+    /// Returns a `Vec<NextHopObject>` containing the nexthop objects and groups on the system.
+    #[cfg(target_os = "linux")]
+    pub async fn list_nexthops(&self) -> io::Result<Vec<NextHopObject>> {
+        self.0.list_nexthops().await
+    }
+
+    /// Add a nexthop object or group. Fails if one with the same id exists.
+    #[cfg(target_os = "linux")]
+    pub async fn add_nexthop(&self, nexthop: &NextHopObject) -> io::Result<()> {
+        self.0.add_nexthop(nexthop).await
+    }
+
+    /// Add a nexthop object or group, replacing the one with the same id if it exists.
+    ///
+    /// Routes using the nexthop object follow the change.
+    #[cfg(target_os = "linux")]
+    pub async fn replace_nexthop(&self, nexthop: &NextHopObject) -> io::Result<()> {
+        self.0.replace_nexthop(nexthop).await
+    }
+
+    /// Remove a nexthop object or group, along with the routes using it.
+    #[cfg(target_os = "linux")]
+    pub async fn delete_nexthop(&self, id: u32) -> io::Result<()> {
+        self.0.delete_nexthop(id).await
+    }
+
+    /// Returns a `Stream` which will yield a `NextHopChange` event whenever a nexthop object is added or removed.
+    #[cfg(target_os = "linux")]
+    pub fn nexthop_listen_stream(&self) -> impl futures::Stream<Item = NextHopChange> {
+        self.0.nexthop_listen_stream()
+    }
+
+    /// Returns a `Vec<MplsRoute>` containing the routes of the MPLS forwarding table.
+    #[cfg(target_os = "linux")]
+    pub async fn list_mpls_routes(&self) -> io::Result<Vec<MplsRoute>> {
+        self.0.list_mpls_routes().await
+    }
+
+    /// Add a route to the MPLS forwarding table.
+    #[cfg(target_os = "linux")]
+    pub async fn add_mpls_route(&self, route: &MplsRoute) -> io::Result<()> {
+        self.0.add_mpls_route(route).await
+    }
+
+    /// Remove the route for `route.label` from the MPLS forwarding table.
+    #[cfg(target_os = "linux")]
+    pub async fn delete_mpls_route(&self, route: &MplsRoute) -> io::Result<()> {
+        self.0.delete_mpls_route(route).await
+    }
+
+    /// Returns a `Vec<MulticastRoute>` containing the entries of the IPv4 and IPv6 multicast
+    /// forwarding caches.
+    #[cfg(target_os = "linux")]
+    pub async fn list_multicast_routes(&self) -> io::Result<Vec<MulticastRoute>> {
+        self.0.list_multicast_routes().await
+    }
+
+    /// Add an entry to the IPv4 multicast forwarding cache, or update the existing one.
+    ///
+    /// The output interfaces need to be configured as virtual interfaces by a multicast routing
+    /// daemon. The kernel doesn't support adding IPv6 entries over netlink.
+    #[cfg(target_os = "linux")]
+    pub async fn add_multicast_route(&self, route: &MulticastRoute) -> io::Result<()> {
+        self.0.add_multicast_route(route).await
+    }
+
+    /// Remove an entry from the IPv4 multicast forwarding cache.
+    #[cfg(target_os = "linux")]
+    pub async fn delete_multicast_route(&self, route: &MulticastRoute) -> io::Result<()> {
+        self.0.delete_multicast_route(route).await
+    }
+
+    /// Returns a `Stream` which will yield a `MplsRouteChange` event whenever an MPLS route is added or removed.
+    #[cfg(target_os = "linux")]
+    pub fn mpls_route_listen_stream(&self) -> impl futures::Stream<Item = MplsRouteChange> {
+        self.0.mpls_route_listen_stream()
+    }
 }
 
 /// Contains information that describes a route in the local computer's Ipv4 or Ipv6 routing table.
@@ -92,6 +290,8 @@ pub struct Route {
     /// The address of the next hop of this route.
     ///
     /// On macOS, this must be `Some` if ifindex is `None`
+    ///
+    /// On Linux, IPv4 routes may use an IPv6 gateway.
     pub gateway: Option<IpAddr>,
 
     /// The index of the local interface through which the next hop of this route may be reached.
@@ -99,9 +299,28 @@ pub struct Route {
     /// On macOS, this must be `Some` if gateway is `None`
     pub ifindex: Option<u32>,
 
+    /// The next hops of a multipath (ECMP) route.
+    ///
+    /// Empty for routes with a single next hop, which is described by `gateway` and `ifindex` instead.
+    #[cfg(target_os = "linux")]
+    pub nexthops: Vec<NextHop>,
+
+    /// Id of the nexthop object (see [`NextHopObject`]) this route forwards through.
+    ///
+    /// Listed routes also report the next hops of the object. They aren't sent to the kernel for
+    /// routes with a nexthop object, as it rejects them.
+    #[cfg(target_os = "linux")]
+    pub nexthop_id: Option<u32>,
+
+    /// Lightweight tunnel encapsulation applied to packets using this route.
+    #[cfg(target_os = "linux")]
+    pub encap: Option<Encap>,
+
     #[cfg(target_os = "linux")]
     /// The routing table this route belongs to.
-    pub table: u8,
+    ///
+    /// Tables above 255 (e.g. VRF tables) are carried in the `RTA_TABLE` attribute.
+    pub table: u32,
 
     /// Network address of the source.
     #[cfg(target_os = "linux")]
@@ -115,10 +334,53 @@ pub struct Route {
     #[cfg(target_os = "linux")]
     pub source_hint: Option<IpAddr>,
 
+    /// The type of this route, e.g. unicast, blackhole or unreachable.
+    #[cfg(target_os = "linux")]
+    pub kind: RouteType,
+
+    /// The TOS (or DSCP) value matched by this route. Part of the route key, 0 matches any value.
+    #[cfg(target_os = "linux")]
+    pub tos: u8,
+
+    /// Routing realms of an IPv4 route (`RTA_FLOW`), used for traffic accounting and policy.
+    #[cfg(target_os = "linux")]
+    pub realm: Option<RouteRealm>,
+
+    /// The origin of this route, e.g. the kernel, a DHCP client or a routing daemon.
+    #[cfg(target_os = "linux")]
+    pub protocol: RouteProtocol,
+
+    /// The distance to the destination covered by this route.
+    ///
+    /// If `None` when adding a route, the scope is derived from the route type and gateway
+    /// the same way `ip route` does it.
+    #[cfg(target_os = "linux")]
+    pub scope: Option<RouteScope>,
+
     #[cfg(any(target_os = "windows", target_os = "linux"))]
     /// The route metric offset value for this route.
     pub metric: Option<u32>,
 
+    /// Per-route metrics such as the MTU or initial congestion window.
+    #[cfg(target_os = "linux")]
+    pub metrics: RouteMetrics,
+
+    /// Route flags. For routes with a single next hop, this includes the flags of that next hop.
+    #[cfg(target_os = "linux")]
+    pub flags: RouteFlags,
+
+    /// Router preference of an IPv6 route, as learned from router advertisements (RFC 4191).
+    #[cfg(target_os = "linux")]
+    pub preference: Option<RoutePreference>,
+
+    /// Remaining lifetime of an IPv6 route, after which the kernel removes it.
+    #[cfg(target_os = "linux")]
+    pub expires: Option<Duration>,
+
+    /// Cache information reported by the kernel. Ignored when adding a route.
+    #[cfg(target_os = "linux")]
+    pub cache_info: Option<RouteCacheInfo>,
+
     #[cfg(target_os = "windows")]
     /// Luid of the local interface through which the next hop of this route may be reached.
     ///
@@ -137,6 +399,12 @@ impl Route {
             gateway: None,
             ifindex: None,
             #[cfg(target_os = "linux")]
+            nexthops: Vec::new(),
+            #[cfg(target_os = "linux")]
+            nexthop_id: None,
+            #[cfg(target_os = "linux")]
+            encap: None,
+            #[cfg(target_os = "linux")]
             // default to main table
             table: 254,
             #[cfg(target_os = "linux")]
@@ -145,8 +413,28 @@ impl Route {
             source_prefix: 0,
             #[cfg(target_os = "linux")]
             source_hint: None,
+            #[cfg(target_os = "linux")]
+            kind: RouteType::Unicast,
+            #[cfg(target_os = "linux")]
+            tos: 0,
+            #[cfg(target_os = "linux")]
+            realm: None,
+            #[cfg(target_os = "linux")]
+            protocol: RouteProtocol::Static,
+            #[cfg(target_os = "linux")]
+            scope: None,
             #[cfg(any(target_os = "windows", target_os = "linux"))]
             metric: None,
+            #[cfg(target_os = "linux")]
+            metrics: RouteMetrics::default(),
+            #[cfg(target_os = "linux")]
+            flags: RouteFlags::empty(),
+            #[cfg(target_os = "linux")]
+            preference: None,
+            #[cfg(target_os = "linux")]
+            expires: None,
+            #[cfg(target_os = "linux")]
+            cache_info: None,
             #[cfg(target_os = "windows")]
             luid: None,
         }
@@ -164,9 +452,30 @@ impl Route {
         self
     }
 
+    /// Add a next hop, turning this route into a multipath route.
+    #[cfg(target_os = "linux")]
+    pub fn with_nexthop(mut self, nexthop: NextHop) -> Self {
+        self.nexthops.push(nexthop);
+        self
+    }
+
+    /// Forward through a nexthop object instead of a gateway or interface of its own.
+    #[cfg(target_os = "linux")]
+    pub fn with_nexthop_id(mut self, id: u32) -> Self {
+        self.nexthop_id = Some(id);
+        self
+    }
+
+    /// Set the encapsulation applied to packets using this route.
+    #[cfg(target_os = "linux")]
+    pub fn with_encap(mut self, encap: Encap) -> Self {
+        self.encap = Some(encap);
+        self
+    }
+
     /// Set table the route will be installed in.
     #[cfg(target_os = "linux")]
-    pub fn with_table(mut self, table: u8) -> Self {
+    pub fn with_table(mut self, table: u32) -> Self {
         self.table = table;
         self
     }
@@ -186,6 +495,41 @@ impl Route {
         self
     }
 
+    /// Set the type of the route.
+    #[cfg(target_os = "linux")]
+    pub fn with_kind(mut self, kind: RouteType) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Set the TOS value matched by the route.
+    #[cfg(target_os = "linux")]
+    pub fn with_tos(mut self, tos: u8) -> Self {
+        self.tos = tos;
+        self
+    }
+
+    /// Set the routing realms of an IPv4 route.
+    #[cfg(target_os = "linux")]
+    pub fn with_realm(mut self, realm: RouteRealm) -> Self {
+        self.realm = Some(realm);
+        self
+    }
+
+    /// Set the protocol the route is tagged with.
+    #[cfg(target_os = "linux")]
+    pub fn with_protocol(mut self, protocol: RouteProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+
+    /// Set the scope of the route.
+    #[cfg(target_os = "linux")]
+    pub fn with_scope(mut self, scope: RouteScope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
     /// Set route metric.
     #[cfg(any(target_os = "windows", target_os = "linux"))]
     pub fn with_metric(mut self, metric: u32) -> Self {
@@ -193,6 +537,34 @@ impl Route {
         self
     }
 
+    /// Set per-route metrics.
+    #[cfg(target_os = "linux")]
+    pub fn with_metrics(mut self, metrics: RouteMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Set route flags, e.g. [`RouteFlags::ONLINK`].
+    #[cfg(target_os = "linux")]
+    pub fn with_flags(mut self, flags: RouteFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Set the router preference of an IPv6 route.
+    #[cfg(target_os = "linux")]
+    pub fn with_preference(mut self, preference: RoutePreference) -> Self {
+        self.preference = Some(preference);
+        self
+    }
+
+    /// Set the lifetime of an IPv6 route, with a resolution of one second.
+    #[cfg(target_os = "linux")]
+    pub fn with_expires(mut self, expires: Duration) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
     /// Set luid of the local interface through which the next hop of this route should be reached.
     #[cfg(target_os = "windows")]
     pub fn with_luid(mut self, luid: u64) -> Self {
@@ -211,6 +583,1194 @@ impl Route {
             )),
         }
     }
+
+    /// Returns `true` if `other` has the key of this route, i.e. removing this route would remove
+    /// `other`. The metric, gateway and interface of this route only narrow the match down when
+    /// set.
+    pub(crate) fn matches_key(&self, other: &Route) -> bool {
+        let mut matches = other.destination == self.destination && other.prefix == self.prefix;
+
+        #[cfg(target_os = "linux")]
+        {
+            // the realm isn't part of the key
+            matches = matches
+                && other.table == self.table
+                && other.tos == self.tos
+                && other.source == self.source
+                && other.source_prefix == self.source_prefix
+                && self
+                    .gateway
+                    .is_none_or(|gateway| other.uses_gateway(gateway))
+                && self
+                    .ifindex
+                    .is_none_or(|ifindex| other.uses_ifindex(ifindex));
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            matches = matches
+                && self
+                    .gateway
+                    .is_none_or(|gateway| other.gateway == Some(gateway))
+                && self
+                    .ifindex
+                    .is_none_or(|ifindex| other.ifindex == Some(ifindex));
+        }
+
+        // the linux kernel omits a metric of 0 for ipv4 routes
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        {
+            matches = matches
+                && self
+                    .metric
+                    .is_none_or(|metric| other.metric.unwrap_or(0) == metric);
+        }
+
+        matches
+    }
+
+    /// Returns `true` if `addr` is within the destination network of the route.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn contains(&self, addr: IpAddr) -> bool {
+        match (self.destination, self.mask(), addr) {
+            (IpAddr::V4(dst), IpAddr::V4(mask), IpAddr::V4(addr)) => {
+                u32::from(dst) & u32::from(mask) == u32::from(addr) & u32::from(mask)
+            }
+            (IpAddr::V6(dst), IpAddr::V6(mask), IpAddr::V6(addr)) => {
+                u128::from(dst) & u128::from(mask) == u128::from(addr) & u128::from(mask)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns `true` if the route or one of its next hops goes through the interface.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn uses_ifindex(&self, ifindex: u32) -> bool {
+        self.ifindex == Some(ifindex) || self.nexthops.iter().any(|nh| nh.ifindex == Some(ifindex))
+    }
+
+    /// Returns `true` if the route or one of its next hops goes through the gateway.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn uses_gateway(&self, gateway: IpAddr) -> bool {
+        self.gateway == Some(gateway) || self.nexthops.iter().any(|nh| nh.gateway == Some(gateway))
+    }
+}
+
+/// One of the next hops of a multipath route.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextHop {
+    /// The address of the next hop.
+    pub gateway: Option<IpAddr>,
+
+    /// The index of the local interface through which the next hop may be reached.
+    pub ifindex: Option<u32>,
+
+    /// The share of traffic sent through this next hop relative to the others, from 1 to 256.
+    pub weight: u16,
+
+    /// Flags of this next hop, e.g. [`RouteFlags::ONLINK`] or [`RouteFlags::LINKDOWN`].
+    pub flags: RouteFlags,
+
+    /// Encapsulation applied to packets sent through this next hop.
+    pub encap: Option<Encap>,
+}
+
+#[cfg(target_os = "linux")]
+impl NextHop {
+    /// Create a next hop with a weight of 1.
+    ///
+    /// Either the gateway or interface should be set before adding it to a route.
+    pub fn new() -> Self {
+        Self {
+            gateway: None,
+            ifindex: None,
+            weight: 1,
+            flags: RouteFlags::empty(),
+            encap: None,
+        }
+    }
+
+    /// Set the address of the next hop.
+    pub fn with_gateway(mut self, gateway: IpAddr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /// Set the index of the local interface through which the next hop should be reached.
+    pub fn with_ifindex(mut self, ifindex: u32) -> Self {
+        self.ifindex = Some(ifindex);
+        self
+    }
+
+    /// Set the weight of the next hop.
+    pub fn with_weight(mut self, weight: u16) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Set the flags of the next hop.
+    pub fn with_flags(mut self, flags: RouteFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Set the encapsulation applied to packets sent through the next hop.
+    pub fn with_encap(mut self, encap: Encap) -> Self {
+        self.encap = Some(encap);
+        self
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Default for NextHop {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A route of the MPLS forwarding table, like `ip -f mpls route add 100 as 200 via inet 10.0.0.1 dev eth0`.
+///
+/// Only routes with a single next hop are supported.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MplsRoute {
+    /// Incoming label matched by this route.
+    pub label: u32,
+
+    /// Labels to swap the incoming label with, outermost first. The label is popped if empty.
+    pub out_labels: Vec<u32>,
+
+    /// The address of the next hop.
+    pub via: Option<IpAddr>,
+
+    /// The index of the local interface through which the next hop may be reached.
+    pub ifindex: Option<u32>,
+
+    /// Whether to copy the TTL to the IP header when popping the last label. `None` follows the
+    /// `net.mpls.ip_ttl_propagate` sysctl.
+    pub ttl_propagate: Option<bool>,
+
+    /// The origin of this route.
+    pub protocol: RouteProtocol,
+}
+
+#[cfg(target_os = "linux")]
+impl MplsRoute {
+    /// Create a route that pops the given incoming label.
+    ///
+    /// Either the via address or interface should be set before adding it.
+    pub fn new(label: u32) -> Self {
+        Self {
+            label,
+            out_labels: Vec::new(),
+            via: None,
+            ifindex: None,
+            ttl_propagate: None,
+            protocol: RouteProtocol::Static,
+        }
+    }
+
+    /// Swap the incoming label with these labels.
+    pub fn with_out_labels(mut self, labels: Vec<u32>) -> Self {
+        self.out_labels = labels;
+        self
+    }
+
+    /// Set the address of the next hop.
+    pub fn with_via(mut self, via: IpAddr) -> Self {
+        self.via = Some(via);
+        self
+    }
+
+    /// Set the index of the local interface through which the next hop should be reached.
+    pub fn with_ifindex(mut self, ifindex: u32) -> Self {
+        self.ifindex = Some(ifindex);
+        self
+    }
+
+    /// Set whether the TTL is propagated when popping the last label.
+    pub fn with_ttl_propagate(mut self, propagate: bool) -> Self {
+        self.ttl_propagate = Some(propagate);
+        self
+    }
+
+    /// Set the protocol the route is tagged with.
+    pub fn with_protocol(mut self, protocol: RouteProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MplsRouteChange {
+    Add(MplsRoute),
+    Delete(MplsRoute),
+}
+
+/// An entry of the multicast forwarding cache, like the ones shown by `ip mroute`.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MulticastRoute {
+    /// Source address of the traffic, unspecified for (*, G) entries.
+    pub source: IpAddr,
+
+    /// Multicast group address.
+    pub group: IpAddr,
+
+    /// The index of the interface the traffic is expected on.
+    pub iif: Option<u32>,
+
+    /// Interfaces the traffic is forwarded to.
+    pub oifs: Vec<MulticastOutput>,
+
+    /// The multicast routing table of the entry.
+    pub table: u32,
+
+    /// Entry flags, e.g. [`RouteFlags::UNRESOLVED`] for entries waiting on the routing daemon.
+    pub flags: RouteFlags,
+
+    /// Traffic counters reported by the kernel. Ignored when adding an entry.
+    pub stats: Option<MulticastRouteStats>,
+
+    /// Time since the entry was last used, reported by the kernel. Ignored when adding an entry.
+    pub last_use: Option<Duration>,
+}
+
+#[cfg(target_os = "linux")]
+impl MulticastRoute {
+    /// Create an entry for traffic from `source` to `group` in the default multicast table.
+    pub fn new(source: IpAddr, group: IpAddr) -> Self {
+        Self {
+            source,
+            group,
+            iif: None,
+            oifs: Vec::new(),
+            // RT_TABLE_DEFAULT, the table used without multicast policy routing
+            table: 253,
+            flags: RouteFlags::empty(),
+            stats: None,
+            last_use: None,
+        }
+    }
+
+    /// Set the index of the interface the traffic is expected on.
+    pub fn with_iif(mut self, iif: u32) -> Self {
+        self.iif = Some(iif);
+        self
+    }
+
+    /// Add an interface to forward the traffic to.
+    pub fn with_oif(mut self, oif: MulticastOutput) -> Self {
+        self.oifs.push(oif);
+        self
+    }
+
+    /// Set the multicast routing table of the entry.
+    pub fn with_table(mut self, table: u32) -> Self {
+        self.table = table;
+        self
+    }
+}
+
+/// An output interface of a multicast forwarding cache entry.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastOutput {
+    /// The index of the interface.
+    pub ifindex: u32,
+
+    /// TTL threshold, from 1 to 254. Packets with a lower TTL are not forwarded to the interface.
+    pub ttl: u8,
+}
+
+#[cfg(target_os = "linux")]
+impl MulticastOutput {
+    /// Create an output with a TTL threshold of 1.
+    pub fn new(ifindex: u32) -> Self {
+        Self { ifindex, ttl: 1 }
+    }
+
+    /// Set the TTL threshold.
+    pub fn with_ttl(mut self, ttl: u8) -> Self {
+        self.ttl = ttl;
+        self
+    }
+}
+
+/// Traffic counters of a multicast forwarding cache entry.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastRouteStats {
+    /// Number of forwarded packets.
+    pub packets: u64,
+    /// Number of forwarded bytes.
+    pub bytes: u64,
+    /// Number of packets which arrived on another interface than the expected one.
+    pub wrong_if: u64,
+}
+
+/// Lightweight tunnel encapsulation of a route or next hop (`RTA_ENCAP`).
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Encap {
+    /// Push an MPLS label stack, like `ip route add ... encap mpls 100/200`.
+    Mpls {
+        /// Labels to push, outermost first. Each label is 20 bits.
+        labels: Vec<u32>,
+        /// TTL of the pushed labels. If `None`, it is copied from the IP header or set by the
+        /// `net.mpls.ip_ttl_propagate` sysctl.
+        ttl: Option<u8>,
+    },
+
+    /// Steer packets through an SRv6 segment list, like `ip route add ... encap seg6 mode encap segs ...`.
+    Seg6 {
+        /// How the segment routing header is added to packets.
+        mode: Seg6Mode,
+        /// Segments to visit, in order.
+        segments: Vec<Ipv6Addr>,
+        /// Key id of the HMAC to add to the segment routing header.
+        hmac: Option<u32>,
+    },
+
+    /// Process packets addressed to a local SRv6 segment, like `ip route add ... encap seg6local action End.DX4 ...`.
+    Seg6Local(Seg6Local),
+}
+
+/// How SRv6 encapsulation adds the segment routing header.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seg6Mode {
+    /// Insert the header into the existing IPv6 packet.
+    Inline,
+    /// Wrap the packet in an outer IPv6 header carrying the segment routing header.
+    Encap,
+    /// Wrap the whole L2 frame in an outer IPv6 header.
+    L2Encap,
+    /// Like `Encap`, omitting the first segment from the header (`encap.red`).
+    EncapReduced,
+    /// Like `L2Encap`, omitting the first segment from the header (`l2encap.red`).
+    L2EncapReduced,
+    /// A mode without a named variant.
+    Other(u32),
+}
+
+/// An SRv6 endpoint behaviour (`seg6local`) along with its parameters.
+///
+/// Which parameters are needed depends on the action, e.g. `End.DX4` needs `nh4` and `End.DT6`
+/// needs `table` or `vrf_table`.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seg6Local {
+    /// The endpoint behaviour.
+    pub action: Seg6LocalAction,
+    /// Table to look up the inner packet in.
+    pub table: Option<u32>,
+    /// VRF table to look up the inner packet in.
+    pub vrf_table: Option<u32>,
+    /// IPv4 next hop to forward decapsulated packets to.
+    pub nh4: Option<Ipv4Addr>,
+    /// IPv6 next hop to forward packets to.
+    pub nh6: Option<Ipv6Addr>,
+    /// Input interface index.
+    pub iif: Option<u32>,
+    /// Interface index to send decapsulated frames through.
+    pub oif: Option<u32>,
+    /// Segment list of the header added by binding SID actions such as `End.B6`.
+    pub segments: Vec<Ipv6Addr>,
+    /// Key id of the HMAC to add to the header of binding SID actions.
+    pub hmac: Option<u32>,
+}
+
+#[cfg(target_os = "linux")]
+impl Seg6Local {
+    /// Create an endpoint behaviour without parameters.
+    pub fn new(action: Seg6LocalAction) -> Self {
+        Self {
+            action,
+            table: None,
+            vrf_table: None,
+            nh4: None,
+            nh6: None,
+            iif: None,
+            oif: None,
+            segments: Vec::new(),
+            hmac: None,
+        }
+    }
+
+    /// Set the table to look up the inner packet in.
+    pub fn with_table(mut self, table: u32) -> Self {
+        self.table = Some(table);
+        self
+    }
+
+    /// Set the VRF table to look up the inner packet in.
+    pub fn with_vrf_table(mut self, table: u32) -> Self {
+        self.vrf_table = Some(table);
+        self
+    }
+
+    /// Set the IPv4 next hop.
+    pub fn with_nh4(mut self, nexthop: Ipv4Addr) -> Self {
+        self.nh4 = Some(nexthop);
+        self
+    }
+
+    /// Set the IPv6 next hop.
+    pub fn with_nh6(mut self, nexthop: Ipv6Addr) -> Self {
+        self.nh6 = Some(nexthop);
+        self
+    }
+
+    /// Set the input interface index.
+    pub fn with_iif(mut self, iif: u32) -> Self {
+        self.iif = Some(iif);
+        self
+    }
+
+    /// Set the output interface index.
+    pub fn with_oif(mut self, oif: u32) -> Self {
+        self.oif = Some(oif);
+        self
+    }
+
+    /// Set the segment list of binding SID actions.
+    pub fn with_segments(mut self, segments: Vec<Ipv6Addr>) -> Self {
+        self.segments = segments;
+        self
+    }
+
+    /// Set the HMAC key id of binding SID actions.
+    pub fn with_hmac(mut self, key_id: u32) -> Self {
+        self.hmac = Some(key_id);
+        self
+    }
+}
+
+/// SRv6 endpoint behaviours (RFC 8986).
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seg6LocalAction {
+    /// Move on to the next segment.
+    End,
+    /// Move on to the next segment and forward to an IPv6 next hop.
+    EndX,
+    /// Move on to the next segment and look it up in a table.
+    EndT,
+    /// Decapsulate and forward the L2 frame through an interface.
+    EndDx2,
+    /// Decapsulate and forward the IPv6 packet to a next hop.
+    EndDx6,
+    /// Decapsulate and forward the IPv4 packet to a next hop.
+    EndDx4,
+    /// Decapsulate and look the IPv6 packet up in a table.
+    EndDt6,
+    /// Decapsulate and look the IPv4 packet up in a table.
+    EndDt4,
+    /// Decapsulate and look the IPv4 or IPv6 packet up in a table.
+    EndDt46,
+    /// Insert a segment routing header (binding SID).
+    EndB6,
+    /// Encapsulate with an outer IPv6 header and segment routing header (binding SID).
+    EndB6Encaps,
+    /// Insert an MPLS label stack (binding SID).
+    EndBm,
+    /// Move on to the next segment and look it up in a table if the target isn't local.
+    EndS,
+    /// Remove the segment routing header for a service which doesn't support it (static proxy).
+    EndAs,
+    /// Hide the segment routing header from a service which doesn't support it (masquerading proxy).
+    EndAm,
+    /// Run a BPF program.
+    EndBpf,
+    /// An action without a named variant.
+    Other(u32),
+}
+
+/// A nexthop object, which routes can share by referring to its id with [`Route::nexthop_id`].
+///
+/// This is either a single next hop described by `gateway`, `ifindex` or `blackhole`, or a group
+/// of other nexthop objects when `group` is not empty.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NextHopObject {
+    /// Unique id of the nexthop object. The kernel picks one when adding an object with id 0.
+    pub id: u32,
+
+    /// The address of the next hop.
+    pub gateway: Option<IpAddr>,
+
+    /// The index of the local interface through which the next hop may be reached.
+    pub ifindex: Option<u32>,
+
+    /// Whether a next hop without gateway is an IPv6 one. The gateway's version is used if set.
+    pub ipv6: bool,
+
+    /// Drop packets sent through this next hop.
+    pub blackhole: bool,
+
+    /// The next hop is used by the bridge FDB rather than by routes.
+    pub fdb: bool,
+
+    /// Members of a nexthop group.
+    pub group: Vec<NextHopGroupMember>,
+
+    /// Turn the group into a resilient one, where flows keep their next hop when members change.
+    /// Groups without it use hash-threshold multipath.
+    pub resilient: Option<ResilientGroup>,
+
+    /// Flags of the next hop, e.g. [`RouteFlags::ONLINK`].
+    pub flags: RouteFlags,
+
+    /// The origin of the nexthop object.
+    pub protocol: RouteProtocol,
+}
+
+#[cfg(target_os = "linux")]
+impl NextHopObject {
+    /// Create a nexthop object with the given id.
+    ///
+    /// Either the gateway, interface, blackhole or group members should be set before adding it.
+    pub fn new(id: u32) -> Self {
+        Self {
+            id,
+            gateway: None,
+            ifindex: None,
+            ipv6: false,
+            blackhole: false,
+            fdb: false,
+            group: Vec::new(),
+            resilient: None,
+            flags: RouteFlags::empty(),
+            protocol: RouteProtocol::Static,
+        }
+    }
+
+    /// Set the address of the next hop.
+    pub fn with_gateway(mut self, gateway: IpAddr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /// Set the index of the local interface through which the next hop should be reached.
+    pub fn with_ifindex(mut self, ifindex: u32) -> Self {
+        self.ifindex = Some(ifindex);
+        self
+    }
+
+    /// Make a next hop without gateway an IPv6 one.
+    pub fn with_ipv6(mut self) -> Self {
+        self.ipv6 = true;
+        self
+    }
+
+    /// Make this a blackhole next hop.
+    pub fn with_blackhole(mut self) -> Self {
+        self.blackhole = true;
+        self
+    }
+
+    /// Mark the next hop for use by the bridge FDB.
+    pub fn with_fdb(mut self) -> Self {
+        self.fdb = true;
+        self
+    }
+
+    /// Add a member, turning this into a nexthop group.
+    pub fn with_group_member(mut self, member: NextHopGroupMember) -> Self {
+        self.group.push(member);
+        self
+    }
+
+    /// Make this a resilient nexthop group.
+    pub fn with_resilient(mut self, resilient: ResilientGroup) -> Self {
+        self.resilient = Some(resilient);
+        self
+    }
+
+    /// Set the flags of the next hop.
+    pub fn with_flags(mut self, flags: RouteFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Set the protocol the nexthop object is tagged with.
+    pub fn with_protocol(mut self, protocol: RouteProtocol) -> Self {
+        self.protocol = protocol;
+        self
+    }
+}
+
+/// A member of a nexthop group.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NextHopGroupMember {
+    /// Id of the member nexthop object.
+    pub id: u32,
+
+    /// The share of traffic sent through this member relative to the others, from 1 to 256.
+    pub weight: u16,
+}
+
+#[cfg(target_os = "linux")]
+impl NextHopGroupMember {
+    /// Create a group member with a weight of 1.
+    pub fn new(id: u32) -> Self {
+        Self { id, weight: 1 }
+    }
+
+    /// Set the weight of the member.
+    pub fn with_weight(mut self, weight: u16) -> Self {
+        self.weight = weight;
+        self
+    }
+}
+
+/// Parameters of a resilient nexthop group, which hashes flows into a fixed number of buckets.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResilientGroup {
+    /// Number of hash buckets.
+    pub buckets: u16,
+
+    /// How long a bucket has to be idle before it may be migrated to another member.
+    pub idle_timer: Option<Duration>,
+
+    /// How long the group may stay unbalanced before busy buckets are migrated as well.
+    /// `None` never migrates busy buckets.
+    pub unbalanced_timer: Option<Duration>,
+}
+
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NextHopChange {
+    Add(NextHopObject),
+    Delete(NextHopObject),
+}
+
+/// Per-route metrics (`RTAX_*` values), used to tune TCP and path MTU behaviour for a destination.
+///
+/// Unset metrics are left to the kernel defaults.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteMetrics {
+    /// Bitmask of metrics the kernel must not update by itself, built from the `LOCK_*` constants.
+    pub lock: u32,
+    /// Path MTU.
+    pub mtu: Option<u32>,
+    /// Maximal TCP window to advertise.
+    pub window: Option<u32>,
+    /// Initial RTT estimate.
+    pub rtt: Option<u32>,
+    /// Initial RTT variance estimate.
+    pub rttvar: Option<u32>,
+    /// Initial slow start threshold.
+    pub ssthresh: Option<u32>,
+    /// Clamp for the congestion window.
+    pub cwnd: Option<u32>,
+    /// Maximal segment size to advertise.
+    pub advmss: Option<u32>,
+    /// Maximal reordering on the path.
+    pub reordering: Option<u32>,
+    /// Hop limit of outgoing packets.
+    pub hoplimit: Option<u32>,
+    /// Initial congestion window, in segments.
+    pub initcwnd: Option<u32>,
+    /// Raw `RTAX_FEATURE_*` bits, e.g. to enable ECN.
+    pub features: Option<u32>,
+    /// Minimal TCP retransmission timeout.
+    pub rto_min: Option<u32>,
+    /// Initial receive window, in segments.
+    pub initrwnd: Option<u32>,
+    /// Whether to disable TCP delayed ACKs.
+    pub quickack: Option<u32>,
+    /// Name of the TCP congestion control algorithm to use, e.g. `cubic` or `bbr`.
+    pub congctl: Option<String>,
+    /// Whether to allow TCP fast open without a cookie.
+    pub fastopen_no_cookie: Option<u32>,
+}
+
+#[cfg(target_os = "linux")]
+impl RouteMetrics {
+    pub const LOCK_MTU: u32 = 1 << 2;
+    pub const LOCK_WINDOW: u32 = 1 << 3;
+    pub const LOCK_RTT: u32 = 1 << 4;
+    pub const LOCK_RTTVAR: u32 = 1 << 5;
+    pub const LOCK_SSTHRESH: u32 = 1 << 6;
+    pub const LOCK_CWND: u32 = 1 << 7;
+    pub const LOCK_ADVMSS: u32 = 1 << 8;
+    pub const LOCK_REORDERING: u32 = 1 << 9;
+    pub const LOCK_HOPLIMIT: u32 = 1 << 10;
+    pub const LOCK_INITCWND: u32 = 1 << 11;
+    pub const LOCK_RTO_MIN: u32 = 1 << 13;
+    pub const LOCK_INITRWND: u32 = 1 << 14;
+
+    /// Returns `true` if no metric is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A set of route or next hop flags (`RTNH_F_*` and `RTM_F_*`).
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct RouteFlags(u32);
+
+#[cfg(target_os = "linux")]
+impl RouteFlags {
+    /// The next hop is dead and not used for forwarding.
+    pub const DEAD: Self = Self(1);
+    /// Do a recursive gateway lookup.
+    pub const PERVASIVE: Self = Self(2);
+    /// The gateway is directly reachable through the interface even though it is not in any of
+    /// the interface's subnets.
+    pub const ONLINK: Self = Self(4);
+    /// The next hop is offloaded to hardware.
+    pub const OFFLOAD: Self = Self(8);
+    /// The carrier of the next hop's interface is down.
+    pub const LINKDOWN: Self = Self(16);
+    /// The neighbour entry of the next hop is not resolved.
+    pub const UNRESOLVED: Self = Self(32);
+    /// The next hop traps packets to the CPU.
+    pub const TRAP: Self = Self(64);
+    /// The route is a cloned cache entry.
+    pub const CLONED: Self = Self(0x200);
+    /// The route is offloaded to hardware.
+    pub const ROUTE_OFFLOAD: Self = Self(0x4000);
+    /// The route traps packets to the CPU.
+    pub const ROUTE_TRAP: Self = Self(0x8000);
+    /// Offloading the route to hardware failed.
+    pub const OFFLOAD_FAILED: Self = Self(0x2000_0000);
+
+    /// An empty set of flags.
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Create a set from raw flag bits.
+    pub const fn from_bits(bits: u32) -> Self {
+        Self(bits)
+    }
+
+    /// The raw flag bits of this set.
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Returns `true` if no flag is set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all flags in `other` are set.
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Set the flags in `other`.
+    pub fn insert(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+
+    /// Clear the flags in `other`.
+    pub fn remove(&mut self, other: Self) {
+        self.0 &= !other.0;
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl std::ops::BitOr for RouteFlags {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl std::ops::BitOrAssign for RouteFlags {
+    fn bitor_assign(&mut self, other: Self) {
+        self.insert(other);
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl std::ops::BitAnd for RouteFlags {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        Self(self.0 & other.0)
+    }
+}
+
+/// The source and destination routing realms of a route (`RTA_FLOW`).
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RouteRealm {
+    /// Realm of the traffic's source, 0 if unset.
+    pub source: u16,
+    /// Realm of the traffic's destination.
+    pub destination: u16,
+}
+
+#[cfg(target_os = "linux")]
+impl RouteRealm {
+    /// Create a realm pair.
+    pub fn new(source: u16, destination: u16) -> Self {
+        Self {
+            source,
+            destination,
+        }
+    }
+}
+
+/// The IP version of a route.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IpVersion {
+    V4,
+    V6,
+}
+
+/// Criteria selecting routes. Unset criteria match every route.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteFilter {
+    /// Only match routes of this IP version.
+    pub family: Option<IpVersion>,
+
+    /// Only match routes whose destination is within this network, given as address and prefix
+    /// length.
+    pub destination: Option<(IpAddr, u8)>,
+
+    /// Only match routes of this table.
+    pub table: Option<u32>,
+
+    /// Only match routes with this origin.
+    pub protocol: Option<RouteProtocol>,
+
+    /// Only match routes of this type.
+    pub kind: Option<RouteType>,
+
+    /// Only match routes with a next hop through this interface.
+    pub ifindex: Option<u32>,
+
+    /// Only match routes with a next hop through this gateway.
+    pub gateway: Option<IpAddr>,
+
+    /// Only match routes with this TOS value.
+    pub tos: Option<u8>,
+
+    /// Only match routes with these routing realms.
+    pub realm: Option<RouteRealm>,
+}
+
+#[cfg(target_os = "linux")]
+impl RouteFilter {
+    /// Create a filter matching every route.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match routes of the given IP version.
+    pub fn with_family(mut self, family: IpVersion) -> Self {
+        self.family = Some(family);
+        self
+    }
+
+    /// Only match routes whose destination is within the given network.
+    pub fn with_destination(mut self, destination: IpAddr, prefix: u8) -> Self {
+        self.destination = Some((destination, prefix));
+        self
+    }
+
+    /// Only match routes of the given table.
+    pub fn with_table(mut self, table: u32) -> Self {
+        self.table = Some(table);
+        self
+    }
+
+    /// Only match routes with the given origin.
+    pub fn with_protocol(mut self, protocol: RouteProtocol) -> Self {
+        self.protocol = Some(protocol);
+        self
+    }
+
+    /// Only match routes of the given type.
+    pub fn with_kind(mut self, kind: RouteType) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Only match routes with a next hop through the given interface.
+    pub fn with_ifindex(mut self, ifindex: u32) -> Self {
+        self.ifindex = Some(ifindex);
+        self
+    }
+
+    /// Only match routes with a next hop through the given gateway.
+    pub fn with_gateway(mut self, gateway: IpAddr) -> Self {
+        self.gateway = Some(gateway);
+        self
+    }
+
+    /// Only match routes with the given TOS value.
+    pub fn with_tos(mut self, tos: u8) -> Self {
+        self.tos = Some(tos);
+        self
+    }
+
+    /// Only match routes with the given routing realms.
+    pub fn with_realm(mut self, realm: RouteRealm) -> Self {
+        self.realm = Some(realm);
+        self
+    }
+
+    /// Returns `true` if the filter matches every route of its IP version.
+    pub(crate) fn is_unrestricted(&self) -> bool {
+        *self
+            == Self {
+                family: self.family,
+                ..Self::default()
+            }
+    }
+
+    /// Returns `true` if `route` meets all criteria of the filter.
+    pub fn matches(&self, route: &Route) -> bool {
+        self.family.is_none_or(|family| match family {
+            IpVersion::V4 => route.destination.is_ipv4(),
+            IpVersion::V6 => route.destination.is_ipv6(),
+        }) && self.destination.is_none_or(|(destination, prefix)| {
+            let network = Route::new(destination, prefix);
+            route.destination.is_ipv4() == destination.is_ipv4()
+                && route.prefix >= prefix
+                && network.contains(route.destination)
+        }) && self.table.is_none_or(|table| route.table == table)
+            && self
+                .protocol
+                .is_none_or(|protocol| route.protocol == protocol)
+            && self.kind.is_none_or(|kind| route.kind == kind)
+            && self
+                .ifindex
+                .is_none_or(|ifindex| route.uses_ifindex(ifindex))
+            && self
+                .gateway
+                .is_none_or(|gateway| route.uses_gateway(gateway))
+            && self.tos.is_none_or(|tos| route.tos == tos)
+            && self.realm.is_none_or(|realm| route.realm == Some(realm))
+    }
+}
+
+/// Parameters of a route lookup with [`Handle::get_route`]. Unset parameters are left for the
+/// kernel to choose.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteLookup {
+    /// Source address of the traffic.
+    pub source: Option<IpAddr>,
+
+    /// Index of the interface the traffic arrives on, to look up a forwarded packet.
+    pub iif: Option<u32>,
+
+    /// Index of the interface the traffic has to leave through.
+    pub oif: Option<u32>,
+
+    /// Firewall mark of the traffic.
+    pub mark: Option<u32>,
+
+    /// User id of the socket sending the traffic.
+    pub uid: Option<u32>,
+
+    /// IP protocol of the traffic, e.g. 6 for TCP.
+    pub ip_proto: Option<u8>,
+
+    /// Source port of the traffic.
+    pub sport: Option<u16>,
+
+    /// Destination port of the traffic.
+    pub dport: Option<u16>,
+
+    /// Return the matching FIB entry as configured, instead of the resolved route.
+    pub fibmatch: bool,
+}
+
+#[cfg(target_os = "linux")]
+impl RouteLookup {
+    /// Create a lookup without any parameter set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the source address of the traffic.
+    pub fn with_source(mut self, source: IpAddr) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    /// Set the index of the interface the traffic arrives on.
+    pub fn with_iif(mut self, iif: u32) -> Self {
+        self.iif = Some(iif);
+        self
+    }
+
+    /// Set the index of the interface the traffic has to leave through.
+    pub fn with_oif(mut self, oif: u32) -> Self {
+        self.oif = Some(oif);
+        self
+    }
+
+    /// Set the firewall mark of the traffic.
+    pub fn with_mark(mut self, mark: u32) -> Self {
+        self.mark = Some(mark);
+        self
+    }
+
+    /// Set the user id of the socket sending the traffic.
+    pub fn with_uid(mut self, uid: u32) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Set the IP protocol of the traffic.
+    pub fn with_ip_proto(mut self, ip_proto: u8) -> Self {
+        self.ip_proto = Some(ip_proto);
+        self
+    }
+
+    /// Set the source port of the traffic.
+    pub fn with_sport(mut self, sport: u16) -> Self {
+        self.sport = Some(sport);
+        self
+    }
+
+    /// Set the destination port of the traffic.
+    pub fn with_dport(mut self, dport: u16) -> Self {
+        self.dport = Some(dport);
+        self
+    }
+
+    /// Return the matching FIB entry instead of the resolved route.
+    pub fn with_fibmatch(mut self, fibmatch: bool) -> Self {
+        self.fibmatch = fibmatch;
+        self
+    }
+}
+
+/// Router preference of an IPv6 route (RFC 4191).
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutePreference {
+    Low,
+    Medium,
+    High,
+}
+
+/// Cache information the kernel keeps for a route (`RTA_CACHEINFO`).
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RouteCacheInfo {
+    /// Number of references to the route.
+    pub clntref: u32,
+    /// Time since the route was last used, in clock ticks.
+    pub last_use: u32,
+    /// Time until the route expires, in clock ticks. Zero if it doesn't expire.
+    pub expires: u32,
+    /// The error reported for packets matching the route.
+    pub error: u32,
+    /// Number of times the route was used.
+    pub used: u32,
+    pub id: u32,
+    pub ts: u32,
+    pub ts_age: u32,
+}
+
+/// The type of a route, which determines what happens to packets matching it.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteType {
+    /// Unknown type.
+    Unspec,
+    /// A regular route through a gateway or directly connected interface.
+    Unicast,
+    /// A route to a local interface address.
+    Local,
+    /// A local broadcast route, sent as broadcast.
+    Broadcast,
+    /// A local broadcast route, sent as unicast.
+    Anycast,
+    /// A multicast route.
+    Multicast,
+    /// Packets are silently dropped.
+    Blackhole,
+    /// Packets are dropped and an ICMP host unreachable error is generated.
+    Unreachable,
+    /// Packets are dropped and an ICMP communication administratively prohibited error is generated.
+    Prohibit,
+    /// The lookup continues in the next table of the policy routing rules.
+    Throw,
+    /// A network address translation rule.
+    Nat,
+    /// Lookup is handed off to an external resolver.
+    ExternalResolve,
+    /// A type not known to this crate.
+    Other(u8),
+}
+
+/// The origin of a route.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteProtocol {
+    /// Unknown origin.
+    Unspec,
+    /// Installed by an ICMP redirect.
+    Redirect,
+    /// Installed by the kernel, e.g. for directly connected networks.
+    Kernel,
+    /// Installed during boot.
+    Boot,
+    /// Installed by the administrator.
+    Static,
+    Gated,
+    /// Learned from IPv6 router advertisements.
+    Ra,
+    Mrt,
+    Zebra,
+    Bird,
+    DnRouted,
+    Xorp,
+    Ntk,
+    /// Installed by a DHCP client.
+    Dhcp,
+    Mrouted,
+    Keepalived,
+    Babel,
+    Bgp,
+    Isis,
+    Ospf,
+    Rip,
+    Eigrp,
+    /// A protocol number without a named variant, e.g. one picked by a routing daemon.
+    Other(u8),
+}
+
+/// The scope of a route's destination.
+#[cfg(target_os = "linux")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RouteScope {
+    /// A global route.
+    Universe,
+    /// An interior route within the local autonomous system.
+    Site,
+    /// A route to a directly attached network.
+    Link,
+    /// A route to an address on the local host.
+    Host,
+    /// The destination doesn't exist.
+    Nowhere,
+    /// A scope without a named variant.
+    Other(u8),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -0,0 +1,425 @@
+//! Lightweight tunnel encapsulation (`RTA_ENCAP`) of routes and next hops.
+//!
+//! netlink-packet-route only knows the MPLS attributes, SRv6 ones are encoded here.
+
+use crate::{Encap, Seg6Local, Seg6LocalAction, Seg6Mode};
+use std::io::{self, Error};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use netlink_packet_route::route::{
+    MplsLabel, RouteAttribute, RouteLwEnCapType, RouteLwTunnelEncap, RouteMplsIpTunnel,
+};
+use netlink_packet_utils::nla::{DefaultNla, Nla};
+
+const MPLS_IPTUNNEL_DST: u16 = 1;
+const MPLS_IPTUNNEL_TTL: u16 = 2;
+
+/// Largest MPLS label, labels are 20 bits.
+const MPLS_LABEL_MAX: u32 = (1 << 20) - 1;
+
+const SEG6_IPTUNNEL_SRH: u16 = 1;
+
+const SEG6_IPTUN_MODE_INLINE: u32 = 0;
+const SEG6_IPTUN_MODE_ENCAP: u32 = 1;
+const SEG6_IPTUN_MODE_L2ENCAP: u32 = 2;
+const SEG6_IPTUN_MODE_ENCAP_RED: u32 = 3;
+const SEG6_IPTUN_MODE_L2ENCAP_RED: u32 = 4;
+
+const SEG6_LOCAL_ACTION: u16 = 1;
+const SEG6_LOCAL_SRH: u16 = 2;
+const SEG6_LOCAL_TABLE: u16 = 3;
+const SEG6_LOCAL_NH4: u16 = 4;
+const SEG6_LOCAL_NH6: u16 = 5;
+const SEG6_LOCAL_IIF: u16 = 6;
+const SEG6_LOCAL_OIF: u16 = 7;
+const SEG6_LOCAL_VRFTABLE: u16 = 9;
+
+/// Length of `struct ipv6_sr_hdr` without its segments.
+const SRH_HEADER_LEN: usize = 8;
+/// IPV6_SRCRT_TYPE_4, the segment routing header.
+const SRH_TYPE: u8 = 4;
+/// SR6_FLAG1_HMAC
+const SRH_FLAG_HMAC: u8 = 1 << 3;
+/// SR6_TLV_HMAC, and the length of `struct sr6_tlv_hmac`.
+const SRH_TLV_HMAC: u8 = 5;
+const SRH_TLV_HMAC_LEN: usize = 40;
+/// SR6_TLV_PAD1, the only TLV without a length byte.
+const SRH_TLV_PAD1: u8 = 0;
+
+pub(super) fn encap_attributes(encap: &Encap) -> io::Result<[RouteAttribute; 2]> {
+    let (kind, nlas) = match encap {
+        Encap::Mpls { labels, ttl } => {
+            if labels.is_empty() {
+                return Err(Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "mpls encap needs one or more labels",
+                ));
+            }
+            let labels = mpls_labels(labels)?;
+            let mut nlas = vec![RouteLwTunnelEncap::Mpls(RouteMplsIpTunnel::Destination(
+                labels,
+            ))];
+            if let Some(ttl) = ttl {
+                nlas.push(RouteLwTunnelEncap::Mpls(RouteMplsIpTunnel::Ttl(*ttl)));
+            }
+            (RouteLwEnCapType::Mpls, nlas)
+        }
+        Encap::Seg6 {
+            mode,
+            segments,
+            hmac,
+        } => {
+            let mode = u32::from(*mode);
+            let mut value = mode.to_ne_bytes().to_vec();
+            // inline mode leaves room for the original destination as the last segment
+            let inline = mode == SEG6_IPTUN_MODE_INLINE;
+            value.extend(srh_to_bytes(segments, *hmac, inline)?);
+            (
+                RouteLwEnCapType::Seg6,
+                vec![RouteLwTunnelEncap::Other(DefaultNla::new(
+                    SEG6_IPTUNNEL_SRH,
+                    value,
+                ))],
+            )
+        }
+        Encap::Seg6Local(local) => {
+            let mut nlas = vec![DefaultNla::new(
+                SEG6_LOCAL_ACTION,
+                u32::from(local.action).to_ne_bytes().to_vec(),
+            )];
+            if !local.segments.is_empty() {
+                nlas.push(DefaultNla::new(
+                    SEG6_LOCAL_SRH,
+                    srh_to_bytes(&local.segments, local.hmac, false)?,
+                ));
+            }
+            let values = [
+                (SEG6_LOCAL_TABLE, local.table),
+                (SEG6_LOCAL_VRFTABLE, local.vrf_table),
+                (SEG6_LOCAL_IIF, local.iif),
+                (SEG6_LOCAL_OIF, local.oif),
+            ];
+            nlas.extend(values.into_iter().filter_map(|(kind, value)| {
+                value.map(|value| DefaultNla::new(kind, value.to_ne_bytes().to_vec()))
+            }));
+            if let Some(nh4) = local.nh4 {
+                nlas.push(DefaultNla::new(SEG6_LOCAL_NH4, nh4.octets().to_vec()));
+            }
+            if let Some(nh6) = local.nh6 {
+                nlas.push(DefaultNla::new(SEG6_LOCAL_NH6, nh6.octets().to_vec()));
+            }
+            (
+                RouteLwEnCapType::Seg6Local,
+                nlas.into_iter().map(RouteLwTunnelEncap::Other).collect(),
+            )
+        }
+    };
+    Ok([RouteAttribute::EncapType(kind), RouteAttribute::Encap(nlas)])
+}
+
+/// Build a label stack, with the bottom of stack bit set on the last label as the kernel expects.
+pub(super) fn mpls_labels(labels: &[u32]) -> io::Result<Vec<MplsLabel>> {
+    if labels.iter().any(|label| *label > MPLS_LABEL_MAX) {
+        return Err(Error::new(
+            io::ErrorKind::InvalidInput,
+            "mpls labels are limited to 20 bits",
+        ));
+    }
+    Ok(labels
+        .iter()
+        .enumerate()
+        .map(|(i, label)| MplsLabel {
+            label: *label,
+            traffic_class: 0,
+            bottom_of_stack: i == labels.len() - 1,
+            ttl: 0,
+        })
+        .collect())
+}
+
+/// netlink-packet-route only parses encaps of next hops if the route has the same encap type,
+/// so this reads the raw attribute values rather than relying on the parsed variants.
+pub(super) fn encap_from_netlink(
+    kind: RouteLwEnCapType,
+    nlas: &[RouteLwTunnelEncap],
+) -> Option<Encap> {
+    let nlas = nlas.iter().map(|nla| {
+        let mut value = vec![0; nla.value_len()];
+        nla.emit_value(&mut value);
+        (nla.kind(), value)
+    });
+
+    match kind {
+        RouteLwEnCapType::Mpls => {
+            let mut labels = vec![];
+            let mut ttl = None;
+            for (kind, value) in nlas {
+                match kind {
+                    MPLS_IPTUNNEL_DST => {
+                        labels = value
+                            .chunks_exact(4)
+                            .map(|label| {
+                                MplsLabel::from(u32::from_be_bytes([
+                                    label[0], label[1], label[2], label[3],
+                                ]))
+                                .label
+                            })
+                            .collect();
+                    }
+                    MPLS_IPTUNNEL_TTL => ttl = value.first().copied(),
+                    _ => {}
+                }
+            }
+            Some(Encap::Mpls { labels, ttl })
+        }
+        RouteLwEnCapType::Seg6 => {
+            let (_, value) = nlas
+                .into_iter()
+                .find(|(kind, _)| *kind == SEG6_IPTUNNEL_SRH)?;
+            let mode = u32::from_ne_bytes(value.get(..4)?.try_into().ok()?);
+            let (mut segments, hmac) = srh_from_bytes(&value[4..])?;
+            if mode == SEG6_IPTUN_MODE_INLINE {
+                segments.pop();
+            }
+            Some(Encap::Seg6 {
+                mode: mode.into(),
+                segments,
+                hmac,
+            })
+        }
+        RouteLwEnCapType::Seg6Local => {
+            let mut local = Seg6Local::new(Seg6LocalAction::Other(0));
+            for (kind, value) in nlas {
+                match kind {
+                    SEG6_LOCAL_ACTION => local.action = parse_u32(&value)?.into(),
+                    SEG6_LOCAL_SRH => (local.segments, local.hmac) = srh_from_bytes(&value)?,
+                    SEG6_LOCAL_TABLE => local.table = parse_u32(&value),
+                    SEG6_LOCAL_VRFTABLE => local.vrf_table = parse_u32(&value),
+                    SEG6_LOCAL_IIF => local.iif = parse_u32(&value),
+                    SEG6_LOCAL_OIF => local.oif = parse_u32(&value),
+                    SEG6_LOCAL_NH4 => {
+                        local.nh4 = <[u8; 4]>::try_from(value.as_slice())
+                            .ok()
+                            .map(Ipv4Addr::from)
+                    }
+                    SEG6_LOCAL_NH6 => {
+                        local.nh6 = <[u8; 16]>::try_from(value.as_slice())
+                            .ok()
+                            .map(Ipv6Addr::from)
+                    }
+                    _ => {}
+                }
+            }
+            Some(Encap::Seg6Local(local))
+        }
+        _ => None,
+    }
+}
+
+fn parse_u32(value: &[u8]) -> Option<u32> {
+    Some(u32::from_ne_bytes(value.try_into().ok()?))
+}
+
+/// Build a segment routing header the way `ip route` does, with the segments stored in reverse.
+fn srh_to_bytes(segments: &[Ipv6Addr], hmac: Option<u32>, inline: bool) -> io::Result<Vec<u8>> {
+    let count = segments.len() + inline as usize;
+    let len = SRH_HEADER_LEN + count * 16 + if hmac.is_some() { SRH_TLV_HMAC_LEN } else { 0 };
+    if segments.is_empty() || len > (u8::MAX as usize + 1) * 8 {
+        return Err(Error::new(
+            io::ErrorKind::InvalidInput,
+            "seg6 encap needs between one and 125 segments",
+        ));
+    }
+
+    let mut srh = vec![0; len];
+    // next header is filled in by the kernel
+    srh[1] = (len / 8 - 1) as u8;
+    srh[2] = SRH_TYPE;
+    // segments left and last entry
+    srh[3] = (count - 1) as u8;
+    srh[4] = (count - 1) as u8;
+    if hmac.is_some() {
+        srh[5] = SRH_FLAG_HMAC;
+    }
+    for (i, segment) in segments.iter().enumerate() {
+        let offset = SRH_HEADER_LEN + (count - 1 - i) * 16;
+        srh[offset..offset + 16].copy_from_slice(&segment.octets());
+    }
+    if let Some(key_id) = hmac {
+        let tlv = &mut srh[len - SRH_TLV_HMAC_LEN..];
+        tlv[0] = SRH_TLV_HMAC;
+        tlv[1] = (SRH_TLV_HMAC_LEN - 2) as u8;
+        tlv[4..8].copy_from_slice(&key_id.to_be_bytes());
+    }
+    Ok(srh)
+}
+
+/// Read the segments, in the order they are visited, and HMAC key id of a segment routing header.
+fn srh_from_bytes(srh: &[u8]) -> Option<(Vec<Ipv6Addr>, Option<u32>)> {
+    let len = (*srh.get(1)? as usize + 1) * 8;
+    let srh = srh.get(..len)?;
+    let count = srh[4] as usize + 1;
+    let tlvs_start = SRH_HEADER_LEN + count * 16;
+
+    let segments = srh
+        .get(SRH_HEADER_LEN..tlvs_start)?
+        .chunks_exact(16)
+        .rev()
+        .map(|segment| Ipv6Addr::from(<[u8; 16]>::try_from(segment).unwrap()))
+        .collect();
+
+    let mut hmac = None;
+    let mut tlvs = &srh[tlvs_start..];
+    while let Some(&kind) = tlvs.first() {
+        if kind == SRH_TLV_PAD1 {
+            tlvs = &tlvs[1..];
+            continue;
+        }
+        let tlv_len = *tlvs.get(1)? as usize + 2;
+        if kind == SRH_TLV_HMAC {
+            hmac = Some(u32::from_be_bytes(tlvs.get(4..8)?.try_into().ok()?));
+        }
+        tlvs = tlvs.get(tlv_len..)?;
+    }
+    Some((segments, hmac))
+}
+
+impl From<Seg6Mode> for u32 {
+    fn from(mode: Seg6Mode) -> Self {
+        match mode {
+            Seg6Mode::Inline => SEG6_IPTUN_MODE_INLINE,
+            Seg6Mode::Encap => SEG6_IPTUN_MODE_ENCAP,
+            Seg6Mode::L2Encap => SEG6_IPTUN_MODE_L2ENCAP,
+            Seg6Mode::EncapReduced => SEG6_IPTUN_MODE_ENCAP_RED,
+            Seg6Mode::L2EncapReduced => SEG6_IPTUN_MODE_L2ENCAP_RED,
+            Seg6Mode::Other(mode) => mode,
+        }
+    }
+}
+
+impl From<u32> for Seg6Mode {
+    fn from(mode: u32) -> Self {
+        match mode {
+            SEG6_IPTUN_MODE_INLINE => Self::Inline,
+            SEG6_IPTUN_MODE_ENCAP => Self::Encap,
+            SEG6_IPTUN_MODE_L2ENCAP => Self::L2Encap,
+            SEG6_IPTUN_MODE_ENCAP_RED => Self::EncapReduced,
+            SEG6_IPTUN_MODE_L2ENCAP_RED => Self::L2EncapReduced,
+            other => Self::Other(other),
+        }
+    }
+}
+
+const SEG6_LOCAL_ACTIONS: [Seg6LocalAction; 16] = [
+    Seg6LocalAction::End,
+    Seg6LocalAction::EndX,
+    Seg6LocalAction::EndT,
+    Seg6LocalAction::EndDx2,
+    Seg6LocalAction::EndDx6,
+    Seg6LocalAction::EndDx4,
+    Seg6LocalAction::EndDt6,
+    Seg6LocalAction::EndDt4,
+    Seg6LocalAction::EndB6,
+    Seg6LocalAction::EndB6Encaps,
+    Seg6LocalAction::EndBm,
+    Seg6LocalAction::EndS,
+    Seg6LocalAction::EndAs,
+    Seg6LocalAction::EndAm,
+    Seg6LocalAction::EndBpf,
+    Seg6LocalAction::EndDt46,
+];
+
+impl From<Seg6LocalAction> for u32 {
+    fn from(action: Seg6LocalAction) -> Self {
+        match action {
+            Seg6LocalAction::Other(action) => action,
+            // SEG6_LOCAL_ACTION_END is 1, the others follow in order
+            action => {
+                SEG6_LOCAL_ACTIONS
+                    .iter()
+                    .position(|a| *a == action)
+                    .unwrap() as u32
+                    + 1
+            }
+        }
+    }
+}
+
+impl From<u32> for Seg6LocalAction {
+    fn from(action: u32) -> Self {
+        action
+            .checked_sub(1)
+            .and_then(|i| SEG6_LOCAL_ACTIONS.get(i as usize))
+            .copied()
+            .unwrap_or(Self::Other(action))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encap_attributes, encap_from_netlink, srh_to_bytes};
+    use crate::{Encap, Seg6Local, Seg6LocalAction, Seg6Mode};
+    use netlink_packet_route::route::RouteAttribute;
+
+    fn round_trip(encap: &Encap) -> Option<Encap> {
+        match encap_attributes(encap).unwrap() {
+            [RouteAttribute::EncapType(kind), RouteAttribute::Encap(nlas)] => {
+                encap_from_netlink(kind, &nlas)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn it_round_trips_seg6_encap() {
+        let encap = Encap::Seg6 {
+            mode: Seg6Mode::Encap,
+            segments: vec!["fc00::1".parse().unwrap(), "fc00::2".parse().unwrap()],
+            hmac: Some(7),
+        };
+        assert_eq!(round_trip(&encap), Some(encap));
+
+        let encap = Encap::Seg6 {
+            mode: Seg6Mode::Inline,
+            segments: vec!["fc00::1".parse().unwrap()],
+            hmac: None,
+        };
+        assert_eq!(round_trip(&encap), Some(encap));
+    }
+
+    #[test]
+    fn it_builds_srh_like_iproute2() {
+        let srh = srh_to_bytes(
+            &["fc00::1".parse().unwrap(), "fc00::2".parse().unwrap()],
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(srh.len(), 40);
+        // hdrlen, type 4, segments left and last entry
+        assert_eq!(&srh[1..5], &[4, 4, 1, 1]);
+        // the first segment to visit is stored last
+        assert_eq!(srh[39], 1);
+        assert_eq!(srh[23], 2);
+
+        assert!(srh_to_bytes(&[], None, false).is_err());
+    }
+
+    #[test]
+    fn it_round_trips_seg6local() {
+        let encap = Encap::Seg6Local(
+            Seg6Local::new(Seg6LocalAction::EndDx4).with_nh4("192.168.1.1".parse().unwrap()),
+        );
+        assert_eq!(round_trip(&encap), Some(encap));
+
+        let encap = Encap::Seg6Local(
+            Seg6Local::new(Seg6LocalAction::EndB6Encaps)
+                .with_segments(vec!["fc00::1".parse().unwrap()])
+                .with_hmac(3),
+        );
+        assert_eq!(round_trip(&encap), Some(encap));
+
+        assert_eq!(u32::from(Seg6LocalAction::EndDt46), 16);
+        assert_eq!(Seg6LocalAction::from(99), Seg6LocalAction::Other(99));
+    }
+}
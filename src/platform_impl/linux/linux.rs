@@ -0,0 +1,2046 @@
+use super::encap::{encap_attributes, encap_from_netlink};
+use super::lookup::lookup_message;
+use super::multicast::{
+    is_multicast_route, multicast_route_message, vif_ifindexes, RTNL_FAMILY_IP6MR, RTNL_FAMILY_IPMR,
+};
+use super::nexthop::NexthopHandle;
+use super::socket::{RouteSocket, RTAX_CC_ALGO_NAME};
+use crate::{
+    IpVersion, MplsRoute, MplsRouteChange, MulticastRoute, NextHop, NextHopChange, NextHopObject,
+    Route, RouteCacheInfo, RouteChange, RouteFilter, RouteFlags, RouteLookup, RouteMetrics,
+    RoutePreference, RouteProtocol, RouteRealm, RouteScope, RouteType,
+};
+use std::io::{self, Error};
+
+use async_stream::stream;
+use futures::{channel::mpsc::UnboundedReceiver, stream::TryStreamExt};
+use futures::{Stream, StreamExt};
+use netlink_packet_core::{
+    NetlinkMessage, NetlinkPayload, NLM_F_ACK, NLM_F_APPEND, NLM_F_CREATE, NLM_F_REPLACE,
+    NLM_F_REQUEST,
+};
+use netlink_packet_route::{
+    route::{
+        self as nl_route, RouteAddress, RouteAttribute, RouteFlag, RouteHeader, RouteLwEnCapType,
+        RouteMessage, RouteMetric, RouteNextHop, RouteNextHopFlag, RouteVia,
+    },
+    AddressFamily, RouteNetlinkMessage,
+};
+use netlink_packet_utils::nla::{DefaultNla, Nla};
+use netlink_sys::{AsyncSocket, SocketAddr};
+use std::collections::{hash_map::Entry, HashMap};
+use std::future::Future;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use tokio::{sync::broadcast, task::JoinHandle};
+
+use rtnetlink::{
+    constants::{RTMGRP_IPV4_ROUTE, RTMGRP_IPV6_ROUTE},
+    new_connection_with_socket,
+};
+
+pub struct Handle {
+    handle: rtnetlink::Handle,
+    join_handle: JoinHandle<()>,
+    listen_handle: JoinHandle<()>,
+    tx: broadcast::Sender<RouteChange>,
+    mpls_tx: broadcast::Sender<MplsRouteChange>,
+    nexthops: NexthopHandle,
+    /// Connection on which the kernel applies the filters of dump requests, `None` if it doesn't
+    /// support it. Kept apart from `handle`, as strict checking also validates every other
+    /// request more strictly.
+    strict_handle: Option<(rtnetlink::Handle, JoinHandle<()>)>,
+}
+
+/// RTNLGRP_MPLS_ROUTE, which doesn't fit the legacy group bitmask `RTMGRP_*` constants use.
+const RTNLGRP_MPLS_ROUTE: u32 = 27;
+
+impl Handle {
+    pub(crate) fn new() -> io::Result<Self> {
+        let (mut connection, handle, messages) = new_connection_with_socket::<RouteSocket>()?;
+
+        // These flags specify what kinds of broadcast messages we want to listen for.
+        let mgroup_flags = RTMGRP_IPV4_ROUTE | RTMGRP_IPV6_ROUTE;
+
+        // A netlink socket address is created with said flags.
+        let addr = SocketAddr::new(0, mgroup_flags);
+        // Said address is bound so new conenctions and thus new message broadcasts can be received.
+        connection.socket_mut().socket_mut().bind(&addr)?;
+        // kernels without MPLS don't know the group, nothing will be sent on it there anyway
+        _ = connection
+            .socket_mut()
+            .socket_mut()
+            .add_membership(RTNLGRP_MPLS_ROUTE);
+        let (tx, _) = broadcast::channel::<RouteChange>(16);
+        let (mpls_tx, _) = broadcast::channel::<MplsRouteChange>(16);
+
+        let join_handle = tokio::spawn(connection);
+        let listen_handle = tokio::spawn(Self::listen(messages, tx.clone(), mpls_tx.clone()));
+
+        Ok(Self {
+            handle,
+            join_handle,
+            listen_handle,
+            tx,
+            mpls_tx,
+            nexthops: NexthopHandle::new()?,
+            strict_handle: Self::new_strict_connection()?,
+        })
+    }
+
+    /// Open a connection with `NETLINK_GET_STRICT_CHK` set, for filtered dumps.
+    fn new_strict_connection() -> io::Result<Option<(rtnetlink::Handle, JoinHandle<()>)>> {
+        let (mut connection, handle, _) = new_connection_with_socket::<RouteSocket>()?;
+        // not supported before Linux 4.20
+        if connection
+            .socket_mut()
+            .socket_mut()
+            .set_netlink_get_strict_chk(true)
+            .is_err()
+        {
+            return Ok(None);
+        }
+        Ok(Some((handle, tokio::spawn(connection))))
+    }
+
+    pub(crate) async fn get_route(
+        &self,
+        destination: IpAddr,
+        options: &RouteLookup,
+    ) -> io::Result<Route> {
+        let msg = lookup_message(destination, options)?;
+        let mut req = NetlinkMessage::from(RouteNetlinkMessage::GetRoute(msg));
+        req.header.flags = NLM_F_REQUEST;
+
+        let mut responses = self.handle.clone().request(req).map_err(netlink_error)?;
+
+        while let Some(msg) = responses.next().await {
+            match msg.payload {
+                NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewRoute(msg)) => {
+                    return Ok(msg.into())
+                }
+                NetlinkPayload::Error(e) if e.code.is_some() => return Err(e.to_io()),
+                _ => {}
+            }
+        }
+        Err(Error::new(
+            io::ErrorKind::NotFound,
+            "No route returned by the kernel",
+        ))
+    }
+
+    pub(crate) async fn default_route(&self) -> io::Result<Option<Route>> {
+        let mut routes = self.handle.route().get(rtnetlink::IpVersion::V4).execute();
+
+        while let Some(route) = routes.try_next().await.map_err(netlink_error)? {
+            if route.destination_prefix().is_none() {
+                return Ok(Some(route.into()));
+            }
+        }
+
+        let mut routes = self.handle.route().get(rtnetlink::IpVersion::V6).execute();
+        let mut defaults = vec![];
+
+        while let Some(route) = routes.try_next().await.map_err(netlink_error)? {
+            if route.destination_prefix().is_none() {
+                defaults.push(route.into());
+            }
+        }
+        Ok(merge_multipath(defaults).into_iter().next())
+    }
+
+    pub(crate) async fn list(&self) -> io::Result<Vec<Route>> {
+        let routes = self.list_stream().try_collect().await?;
+        Ok(merge_multipath(routes))
+    }
+
+    pub(crate) fn list_stream(&self) -> impl Stream<Item = io::Result<Route>> {
+        let handle = self.handle.clone();
+
+        stream! {
+            // only send the request once the stream is polled
+            let mut req = handle.route().get(rtnetlink::IpVersion::V4);
+            // a single dump of every family, instead of one dump per IP version
+            req.message_mut().header.address_family = AddressFamily::Unspec;
+            let mut route_messages = req.execute();
+
+            // siblings of IPv6 multipath routes are usually dumped next to each other
+            let mut pending: Option<Route> = None;
+            loop {
+                let msg = match route_messages.try_next().await {
+                    Ok(Some(msg)) => msg,
+                    Ok(None) => break,
+                    Err(e) => {
+                        yield Err(netlink_error(e));
+                        return;
+                    }
+                };
+                if !matches!(
+                    msg.header.address_family,
+                    AddressFamily::Inet | AddressFamily::Inet6
+                ) {
+                    continue;
+                }
+
+                let route = Route::from(msg);
+                match pending.as_mut() {
+                    Some(first) if sibling_key(&route).is_some()
+                        && sibling_key(first) == sibling_key(&route) =>
+                    {
+                        merge_sibling(first, route)
+                    }
+                    _ => {
+                        if let Some(route) = pending.replace(route) {
+                            yield Ok(route);
+                        }
+                    }
+                }
+            }
+            if let Some(route) = pending {
+                yield Ok(route);
+            }
+        }
+    }
+
+    pub(crate) async fn list_filtered(&self, filter: &RouteFilter) -> io::Result<Vec<Route>> {
+        let versions = match filter.family {
+            Some(IpVersion::V4) => &[rtnetlink::IpVersion::V4][..],
+            Some(IpVersion::V6) => &[rtnetlink::IpVersion::V6][..],
+            None => &[rtnetlink::IpVersion::V4, rtnetlink::IpVersion::V6][..],
+        };
+
+        let mut routes = vec![];
+        for version in versions {
+            let req = match &self.strict_handle {
+                Some((handle, _)) => {
+                    let mut req = handle.route().get(version.clone());
+                    set_dump_filter(req.message_mut(), filter);
+                    req
+                }
+                None => self.handle.route().get(version.clone()),
+            };
+            let mut route_messages = req.execute();
+            loop {
+                match route_messages.try_next().await {
+                    Ok(Some(route)) => routes.push(route.into()),
+                    Ok(None) => break,
+                    Err(e) if is_empty_dump_error(&e) => break,
+                    Err(e) => return Err(netlink_error(e)),
+                }
+            }
+        }
+
+        // the kernel can't filter by destination, and doesn't filter at all without strict checks
+        let mut routes = merge_multipath(routes);
+        routes.retain(|route| filter.matches(route));
+        Ok(routes)
+    }
+
+    pub(crate) fn route_listen_stream(&self) -> impl Stream<Item = RouteChange> {
+        let mut rx = self.tx.subscribe();
+        stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(ev) => yield ev,
+                    Err(e) => match e {
+                        broadcast::error::RecvError::Closed => break,
+                        broadcast::error::RecvError::Lagged(_) => continue,
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) async fn delete(&self, route: &Route) -> io::Result<()> {
+        // the kernel compares multipath next hops as a whole, so look up the exact route instead
+        if !route.nexthops.is_empty() {
+            return self.delete_listed(route).await;
+        }
+
+        self.handle
+            .route()
+            .del(delete_message(route)?)
+            .execute()
+            .await
+            .map_err(delete_error)
+    }
+
+    /// Delete the first listed route matching the key of `route`.
+    async fn delete_listed(&self, route: &Route) -> io::Result<()> {
+        let listed = self.list_messages(&[ip_version(route.destination)]).await?;
+        self.delete_from(route, &listed).await
+    }
+
+    /// Delete the first route of `listed` matching the key of `route`.
+    async fn delete_from(&self, route: &Route, listed: &[(RouteMessage, Route)]) -> io::Result<()> {
+        let Some((msg, _)) = listed
+            .iter()
+            .find(|(_, other_route)| route.matches_key(other_route))
+        else {
+            return Err(Error::new(
+                io::ErrorKind::NotFound,
+                "No matching route found to delete",
+            ));
+        };
+        self.handle
+            .route()
+            .del(msg.clone())
+            .execute()
+            .await
+            .map_err(delete_error)
+    }
+
+    /// Dump the routes of `versions`, along with the messages they were read from.
+    async fn list_messages(
+        &self,
+        versions: &[rtnetlink::IpVersion],
+    ) -> io::Result<Vec<(RouteMessage, Route)>> {
+        let mut listed = vec![];
+        for version in versions {
+            let mut routes = self.handle.route().get(version.clone()).execute();
+            while let Some(msg) = routes.try_next().await.map_err(netlink_error)? {
+                let route = msg.clone().into();
+                listed.push((msg, route));
+            }
+        }
+        Ok(listed)
+    }
+
+    pub(crate) async fn delete_matching(
+        &self,
+        filter: &RouteFilter,
+    ) -> io::Result<Vec<(Route, io::Result<()>)>> {
+        // without criteria, the local and broadcast tables would go too
+        if filter.is_unrestricted() {
+            return Err(Error::new(
+                io::ErrorKind::InvalidInput,
+                "filter matches every route",
+            ));
+        }
+
+        let route_handle = self.handle.route();
+        let mut matching = vec![];
+        for version in [rtnetlink::IpVersion::V4, rtnetlink::IpVersion::V6] {
+            let mut routes = route_handle.get(version).execute();
+            while let Some(msg) = routes.try_next().await.map_err(netlink_error)? {
+                let route: Route = msg.clone().into();
+                if filter.matches(&route) {
+                    matching.push((msg, route));
+                }
+            }
+        }
+
+        // keep going after a failure, so that the caller learns about every removed route
+        let mut deleted = vec![];
+        let mut failed = vec![];
+        for (msg, route) in matching {
+            match route_handle.del(msg).execute().await {
+                Ok(()) => deleted.push(route),
+                Err(e) => failed.push((route, netlink_error(e))),
+            }
+        }
+        Ok(deletion_results(deleted, failed))
+    }
+
+    pub(crate) async fn add(&self, route: &Route) -> io::Result<()> {
+        let mut req = self.handle.route().add();
+        *req.message_mut() = route.try_into()?;
+        req.execute().await.map_err(netlink_error)
+    }
+
+    pub(crate) async fn add_many(&self, routes: &[Route], window: usize) -> Vec<io::Result<()>> {
+        pipelined(routes, window, |route| self.add(route)).await
+    }
+
+    pub(crate) async fn delete_many(&self, routes: &[Route], window: usize) -> Vec<io::Result<()>> {
+        // multipath routes are looked up in a single dump for the whole batch, instead of one
+        // dump each like `delete` does
+        let mut versions = vec![];
+        for route in routes.iter().filter(|route| !route.nexthops.is_empty()) {
+            let version = ip_version(route.destination);
+            if !versions.contains(&version) {
+                versions.push(version);
+            }
+        }
+        let listed = if versions.is_empty() {
+            Ok(vec![])
+        } else {
+            self.list_messages(&versions).await
+        };
+
+        pipelined(routes, window, |route| {
+            let listed = &listed;
+            async move {
+                if route.nexthops.is_empty() {
+                    return self.delete(route).await;
+                }
+                match listed {
+                    Ok(listed) => self.delete_from(route, listed).await,
+                    Err(e) => Err(Error::new(e.kind(), e.to_string())),
+                }
+            }
+        })
+        .await
+    }
+
+    pub(crate) async fn append(&self, route: &Route) -> io::Result<()> {
+        let mut req = NetlinkMessage::from(RouteNetlinkMessage::NewRoute(route.try_into()?));
+        req.header.flags = NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_APPEND;
+
+        let mut responses = self.handle.clone().request(req).map_err(netlink_error)?;
+        while let Some(msg) = responses.next().await {
+            if let NetlinkPayload::Error(e) = msg.payload {
+                if e.code.is_some() {
+                    return Err(e.to_io());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn replace(&self, route: &Route) -> io::Result<()> {
+        // NLM_F_CREATE | NLM_F_REPLACE
+        let mut req = self.handle.route().add().replace();
+        *req.message_mut() = route.try_into()?;
+        req.execute().await.map_err(netlink_error)
+    }
+
+    pub(crate) async fn list_nexthops(&self) -> io::Result<Vec<NextHopObject>> {
+        self.nexthops.list().await
+    }
+
+    pub(crate) async fn add_nexthop(&self, nexthop: &NextHopObject) -> io::Result<()> {
+        self.nexthops.add(nexthop).await
+    }
+
+    pub(crate) async fn replace_nexthop(&self, nexthop: &NextHopObject) -> io::Result<()> {
+        self.nexthops.replace(nexthop).await
+    }
+
+    pub(crate) async fn delete_nexthop(&self, id: u32) -> io::Result<()> {
+        self.nexthops.delete(id).await
+    }
+
+    pub(crate) fn nexthop_listen_stream(&self) -> impl Stream<Item = NextHopChange> {
+        self.nexthops.listen_stream()
+    }
+
+    pub(crate) async fn list_mpls_routes(&self) -> io::Result<Vec<MplsRoute>> {
+        let mut req = self.handle.route().get(rtnetlink::IpVersion::V4);
+        req.message_mut().header.address_family = AddressFamily::Mpls;
+        let mut route_messages = req.execute();
+
+        let mut routes = vec![];
+        while let Some(route) = route_messages.try_next().await.map_err(netlink_error)? {
+            // without MPLS support the kernel answers with a dump of all families instead
+            if is_mpls(&route) {
+                routes.push(route.into());
+            }
+        }
+        Ok(routes)
+    }
+
+    pub(crate) async fn add_mpls_route(&self, route: &MplsRoute) -> io::Result<()> {
+        let mut req = self.handle.route().add();
+        *req.message_mut() = route.try_into()?;
+        req.execute().await.map_err(netlink_error)
+    }
+
+    pub(crate) async fn delete_mpls_route(&self, route: &MplsRoute) -> io::Result<()> {
+        let msg = RouteMessage::try_from(&MplsRoute::new(route.label))?;
+        self.handle
+            .route()
+            .del(msg)
+            .execute()
+            .await
+            .map_err(netlink_error)
+    }
+
+    pub(crate) async fn list_multicast_routes(&self) -> io::Result<Vec<MulticastRoute>> {
+        let mut routes = vec![];
+        for family in [RTNL_FAMILY_IPMR, RTNL_FAMILY_IP6MR] {
+            let mut req = self.handle.route().get(rtnetlink::IpVersion::V4);
+            req.message_mut().header.address_family = AddressFamily::Other(family);
+            let mut route_messages = req.execute();
+
+            while let Some(route) = route_messages.try_next().await.map_err(netlink_error)? {
+                // like for MPLS, unsupported families are answered with a dump of all families
+                if is_multicast_route(&route) {
+                    routes.push(route.into());
+                }
+            }
+        }
+        Ok(routes)
+    }
+
+    pub(crate) async fn add_multicast_route(&self, route: &MulticastRoute) -> io::Result<()> {
+        let vifs = if route.oifs.is_empty() {
+            vec![]
+        } else {
+            vif_ifindexes(route.table)?
+        };
+        let mut req = self.handle.route().add().replace();
+        *req.message_mut() = multicast_route_message(route, &vifs)?;
+        req.execute().await.map_err(netlink_error)
+    }
+
+    pub(crate) async fn delete_multicast_route(&self, route: &MulticastRoute) -> io::Result<()> {
+        let msg = multicast_route_message(
+            &MulticastRoute {
+                oifs: vec![],
+                ..route.clone()
+            },
+            &[],
+        )?;
+        self.handle
+            .route()
+            .del(msg)
+            .execute()
+            .await
+            .map_err(netlink_error)
+    }
+
+    pub(crate) fn mpls_route_listen_stream(&self) -> impl Stream<Item = MplsRouteChange> {
+        let mut rx = self.mpls_tx.subscribe();
+        stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(ev) => yield ev,
+                    Err(e) => match e {
+                        broadcast::error::RecvError::Closed => break,
+                        broadcast::error::RecvError::Lagged(_) => continue,
+                    }
+                }
+            }
+        }
+    }
+
+    async fn listen(
+        mut messages: UnboundedReceiver<(NetlinkMessage<RouteNetlinkMessage>, SocketAddr)>,
+        tx: broadcast::Sender<RouteChange>,
+        mpls_tx: broadcast::Sender<MplsRouteChange>,
+    ) {
+        while let Some((message, _)) = messages.next().await {
+            let flags = message.header.flags;
+            if let NetlinkPayload::InnerMessage(msg) = message.payload {
+                match msg {
+                    RouteNetlinkMessage::NewRoute(msg) if is_mpls(&msg) => {
+                        _ = mpls_tx.send(MplsRouteChange::Add(msg.into()))
+                    }
+                    RouteNetlinkMessage::DelRoute(msg) if is_mpls(&msg) => {
+                        _ = mpls_tx.send(MplsRouteChange::Delete(msg.into()))
+                    }
+                    RouteNetlinkMessage::NewRoute(msg) => {
+                        _ = tx.send(new_route_change(flags, msg.into()))
+                    }
+                    RouteNetlinkMessage::DelRoute(msg) => {
+                        _ = tx.send(RouteChange::Delete(msg.into()))
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        self.join_handle.abort();
+        self.listen_handle.abort();
+        if let Some((_, join_handle)) = &self.strict_handle {
+            join_handle.abort();
+        }
+    }
+}
+
+/// Set the header fields and attributes the kernel filters route dumps by with
+/// `NETLINK_GET_STRICT_CHK`.
+fn set_dump_filter(msg: &mut RouteMessage, filter: &RouteFilter) {
+    if let Some(table) = filter.table {
+        match u8::try_from(table) {
+            Ok(table) => msg.header.table = table,
+            Err(_) => msg.attributes.push(RouteAttribute::Table(table)),
+        }
+    }
+    if let Some(protocol) = filter.protocol {
+        msg.header.protocol = protocol.into();
+    }
+    if let Some(kind) = filter.kind {
+        msg.header.kind = kind.into();
+    }
+    if let Some(ifindex) = filter.ifindex {
+        msg.attributes.push(RouteAttribute::Oif(ifindex));
+    }
+}
+
+/// Run `op` on each of `items` and return the results in the order of `items`.
+///
+/// Each request is sent when its future is first polled, so up to `window` requests are in
+/// flight on the connection at once. A window of 0 is treated as 1.
+async fn pipelined<'a, T, F, Fut>(items: &'a [T], window: usize, op: F) -> Vec<io::Result<()>>
+where
+    F: FnMut(&'a T) -> Fut,
+    Fut: Future<Output = io::Result<()>>,
+{
+    futures::stream::iter(items)
+        .map(op)
+        .buffered(window.max(1))
+        .collect()
+        .await
+}
+
+fn ip_version(addr: IpAddr) -> rtnetlink::IpVersion {
+    match addr {
+        IpAddr::V4(_) => rtnetlink::IpVersion::V4,
+        IpAddr::V6(_) => rtnetlink::IpVersion::V6,
+    }
+}
+
+/// Whether a filtered dump failed because the table or interface it filters by doesn't exist in
+/// the dumped family (ENOENT and ENODEV), meaning that the family has no matching route.
+fn is_empty_dump_error(e: &rtnetlink::Error) -> bool {
+    matches!(e, rtnetlink::Error::NetlinkError(msg) if matches!(msg.raw_code(), -2 | -19))
+}
+
+/// Build an `RTM_DELROUTE` message for the route with the key of `route`.
+///
+/// The kernel treats the type, protocol and scope of the message as wildcards when they are
+/// unspecified, and only narrows the match down by the metric, gateway and interface when set,
+/// which is the same as [`Route::matches_key`].
+fn delete_message(route: &Route) -> io::Result<RouteMessage> {
+    let mut msg = RouteMessage::default();
+    msg.header.address_family = match route.destination {
+        IpAddr::V4(_) => AddressFamily::Inet,
+        IpAddr::V6(_) => AddressFamily::Inet6,
+    };
+    msg.header.destination_prefix_length = route.prefix;
+    msg.header.tos = route.tos;
+    msg.header.kind = nl_route::RouteType::Unspec;
+    msg.header.protocol = nl_route::RouteProtocol::Unspec;
+    msg.header.scope = nl_route::RouteScope::NoWhere;
+
+    match u8::try_from(route.table) {
+        Ok(table) => msg.header.table = table,
+        Err(_) => {
+            msg.header.table = RouteHeader::RT_TABLE_UNSPEC;
+            msg.attributes.push(RouteAttribute::Table(route.table));
+        }
+    }
+
+    msg.attributes
+        .push(RouteAttribute::Destination(ip_to_addr(route.destination)));
+
+    if let Some(src) = route.source {
+        check_family(
+            route.destination,
+            src,
+            "source version must match destination",
+        )?;
+        msg.header.source_prefix_length = route.source_prefix;
+        msg.attributes.push(RouteAttribute::Source(ip_to_addr(src)));
+    }
+
+    if let Some(metric) = route.metric {
+        msg.attributes.push(RouteAttribute::Priority(metric));
+    }
+
+    if let Some(gateway) = route.gateway {
+        msg.attributes
+            .push(gateway_attribute(route.destination, gateway)?);
+    }
+
+    if let Some(ifindex) = route.ifindex {
+        msg.attributes.push(RouteAttribute::Oif(ifindex));
+    }
+
+    Ok(msg)
+}
+
+/// The kernel sets `NLM_F_REPLACE` on the notifications of routes which replaced an existing
+/// route with the same key, and `NLM_F_APPEND` on those of next hops appended to one.
+fn new_route_change(flags: u16, route: Route) -> RouteChange {
+    if flags & (NLM_F_REPLACE | NLM_F_APPEND) != 0 {
+        RouteChange::Change(route)
+    } else {
+        RouteChange::Add(route)
+    }
+}
+
+/// Keep the errno of errors reported by the kernel, so that e.g. `EEXIST` maps to
+/// [`io::ErrorKind::AlreadyExists`].
+fn netlink_error(e: rtnetlink::Error) -> io::Error {
+    match e {
+        rtnetlink::Error::NetlinkError(msg) => msg.to_io(),
+        e => Error::other(e.to_string()),
+    }
+}
+
+fn delete_error(e: rtnetlink::Error) -> io::Error {
+    match e {
+        // ESRCH
+        rtnetlink::Error::NetlinkError(msg) if msg.raw_code() == -3 => {
+            Error::new(io::ErrorKind::NotFound, "No matching route found to delete")
+        }
+        e => netlink_error(e),
+    }
+}
+
+fn is_mpls(msg: &RouteMessage) -> bool {
+    msg.header.address_family == AddressFamily::Mpls
+}
+
+fn addr_to_ip(addr: RouteAddress) -> Option<IpAddr> {
+    match addr {
+        RouteAddress::Inet(addr) => Some(addr.into()),
+        RouteAddress::Inet6(addr) => Some(addr.into()),
+        _ => None,
+    }
+}
+
+impl From<RouteMessage> for Route {
+    fn from(msg: RouteMessage) -> Self {
+        let mut gateway = None;
+        let mut source = None;
+        let mut source_hint = None;
+        let mut destination = None;
+        let mut ifindex = None;
+        let mut nexthops = vec![];
+        let mut nexthop_id = None;
+        let mut encap_type = RouteLwEnCapType::None;
+        let mut encap = vec![];
+        let mut metric = None;
+        let mut metrics = RouteMetrics::default();
+        let mut preference = None;
+        let mut expires = None;
+        let mut cache_info = None;
+        let mut realm = None;
+        // the header only has room for 8 bits, RTA_TABLE holds the full id when present
+        let mut table = msg.header.table as u32;
+
+        for attr in msg.attributes {
+            match attr {
+                RouteAttribute::Source(addr) => {
+                    source = addr_to_ip(addr);
+                }
+                RouteAttribute::PrefSource(addr) => {
+                    source_hint = addr_to_ip(addr);
+                }
+                RouteAttribute::Destination(addr) => {
+                    destination = addr_to_ip(addr);
+                }
+                RouteAttribute::Gateway(addr) => {
+                    gateway = addr_to_ip(addr);
+                }
+                RouteAttribute::Via(via) => {
+                    gateway = via_to_ip(via);
+                }
+                RouteAttribute::Oif(i) => {
+                    ifindex = Some(i);
+                }
+                RouteAttribute::MultiPath(hops) => {
+                    nexthops = hops.into_iter().map(NextHop::from).collect();
+                }
+                RouteAttribute::Other(nla) if nla.kind() == RTA_NH_ID => {
+                    let mut id = [0; 4];
+                    if nla.value_len() == id.len() {
+                        nla.emit_value(&mut id);
+                        nexthop_id = Some(u32::from_ne_bytes(id));
+                    }
+                }
+                RouteAttribute::EncapType(kind) => {
+                    encap_type = kind;
+                }
+                RouteAttribute::Encap(nlas) => {
+                    encap = nlas;
+                }
+                RouteAttribute::Priority(priority) => {
+                    metric = Some(priority);
+                }
+                RouteAttribute::Table(id) => {
+                    table = id;
+                }
+                RouteAttribute::Realm(realms) => {
+                    realm = Some(RouteRealm::new(realms.source, realms.destination));
+                }
+                RouteAttribute::Metrics(values) => {
+                    metrics = values.into();
+                }
+                RouteAttribute::Preference(pref) => {
+                    preference = match pref {
+                        nl_route::RoutePreference::Low => Some(RoutePreference::Low),
+                        nl_route::RoutePreference::Medium => Some(RoutePreference::Medium),
+                        nl_route::RoutePreference::High => Some(RoutePreference::High),
+                        _ => None,
+                    };
+                }
+                RouteAttribute::Expires(secs) => {
+                    expires = Some(Duration::from_secs(secs.into()));
+                }
+                RouteAttribute::CacheInfo(info) => {
+                    cache_info = Some(RouteCacheInfo {
+                        clntref: info.clntref,
+                        last_use: info.last_use,
+                        expires: info.expires,
+                        error: info.error,
+                        used: info.used,
+                        id: info.id,
+                        ts: info.ts,
+                        ts_age: info.ts_age,
+                    });
+                }
+                _ => {}
+            }
+        }
+        // rtnetlink gives None instead of 0.0.0.0 for the default route, but we'll convert to 0 here to make it match the other platforms
+        let destination = destination.unwrap_or_else(|| match msg.header.address_family {
+            AddressFamily::Inet => Ipv4Addr::UNSPECIFIED.into(),
+            AddressFamily::Inet6 => Ipv6Addr::UNSPECIFIED.into(),
+            _ => panic!("invalid destination family"),
+        });
+        // dumps only carry the remaining lifetime in the cache info
+        let expires = expires.or_else(|| {
+            cache_info
+                .filter(|info| info.expires != 0)
+                .map(|info| clock_ticks_to_duration(info.expires))
+        });
+        Self {
+            destination,
+            prefix: msg.header.destination_prefix_length,
+            source,
+            source_prefix: msg.header.source_prefix_length,
+            source_hint,
+            gateway,
+            ifindex,
+            nexthops,
+            nexthop_id,
+            encap: encap_from_netlink(encap_type, &encap),
+            table,
+            kind: msg.header.kind.into(),
+            tos: msg.header.tos,
+            realm,
+            protocol: msg.header.protocol.into(),
+            scope: Some(msg.header.scope.into()),
+            metric,
+            metrics,
+            flags: msg.header.flags.as_slice().into(),
+            preference,
+            expires,
+            cache_info,
+        }
+    }
+}
+
+pub(super) fn ip_to_addr(addr: IpAddr) -> RouteAddress {
+    match addr {
+        IpAddr::V4(addr) => RouteAddress::Inet(addr),
+        IpAddr::V6(addr) => RouteAddress::Inet6(addr),
+    }
+}
+
+fn via_to_ip(via: RouteVia) -> Option<IpAddr> {
+    match via {
+        RouteVia::Inet(addr) => Some(addr.into()),
+        RouteVia::Inet6(addr) => Some(addr.into()),
+        _ => None,
+    }
+}
+
+/// IPv4 routes may use IPv6 gateways (RFC 5549), which have to be sent as RTA_VIA since
+/// RTA_GATEWAY must match the family of the route. IPv6 routes have no such support.
+fn gateway_attribute(destination: IpAddr, gateway: IpAddr) -> io::Result<RouteAttribute> {
+    match (destination, gateway) {
+        (IpAddr::V4(_), IpAddr::V6(addr)) => Ok(RouteAttribute::Via(RouteVia::Inet6(addr))),
+        _ => {
+            check_family(
+                destination,
+                gateway,
+                "gateway version must match destination",
+            )?;
+            Ok(RouteAttribute::Gateway(ip_to_addr(gateway)))
+        }
+    }
+}
+
+pub(super) fn check_family(destination: IpAddr, addr: IpAddr, msg: &str) -> io::Result<()> {
+    if destination.is_ipv4() != addr.is_ipv4() {
+        return Err(Error::new(io::ErrorKind::InvalidInput, msg));
+    }
+    Ok(())
+}
+
+impl TryFrom<&Route> for RouteMessage {
+    type Error = io::Error;
+
+    fn try_from(route: &Route) -> io::Result<Self> {
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = match route.destination {
+            IpAddr::V4(_) => AddressFamily::Inet,
+            IpAddr::V6(_) => AddressFamily::Inet6,
+        };
+        msg.header.destination_prefix_length = route.prefix;
+        msg.header.kind = route.kind.into();
+        msg.header.tos = route.tos;
+        msg.header.protocol = route.protocol.into();
+        msg.header.scope = route_scope(route).into();
+        msg.header.flags = request_flags(route.flags).into();
+
+        // same as `ip route`, ids that don't fit in the header are only sent as RTA_TABLE
+        match u8::try_from(route.table) {
+            Ok(table) => msg.header.table = table,
+            Err(_) => {
+                msg.header.table = RouteHeader::RT_TABLE_UNSPEC;
+                msg.attributes.push(RouteAttribute::Table(route.table));
+            }
+        }
+
+        msg.attributes
+            .push(RouteAttribute::Destination(ip_to_addr(route.destination)));
+
+        // the kernel reports the next hops of nexthop objects along with their id, but rejects
+        // requests combining them
+        let uses_nexthop_id = route.nexthop_id.is_some();
+
+        if let Some(ifindex) = route.ifindex.filter(|_| !uses_nexthop_id) {
+            msg.attributes.push(RouteAttribute::Oif(ifindex));
+        }
+
+        if let Some(metric) = route.metric {
+            msg.attributes.push(RouteAttribute::Priority(metric));
+        }
+
+        if let Some(realm) = route.realm {
+            msg.attributes
+                .push(RouteAttribute::Realm(nl_route::RouteRealm {
+                    source: realm.source,
+                    destination: realm.destination,
+                }));
+        }
+
+        if let Some(encap) = &route.encap {
+            msg.attributes.extend(encap_attributes(encap)?);
+        }
+
+        if let Some(id) = route.nexthop_id {
+            msg.attributes.push(RouteAttribute::Other(DefaultNla::new(
+                RTA_NH_ID,
+                id.to_ne_bytes().to_vec(),
+            )));
+        }
+
+        if let Some(gateway) = route.gateway.filter(|_| !uses_nexthop_id) {
+            msg.attributes
+                .push(gateway_attribute(route.destination, gateway)?);
+        }
+
+        if let Some(src_hint) = route.source_hint {
+            check_family(
+                route.destination,
+                src_hint,
+                "source hint version must match destination",
+            )?;
+            msg.attributes
+                .push(RouteAttribute::PrefSource(ip_to_addr(src_hint)));
+        }
+
+        if let Some(src) = route.source {
+            check_family(
+                route.destination,
+                src,
+                "source version must match destination",
+            )?;
+            msg.header.source_prefix_length = route.source_prefix;
+            msg.attributes.push(RouteAttribute::Source(ip_to_addr(src)));
+        }
+
+        if !route.metrics.is_empty() {
+            msg.attributes
+                .push(RouteAttribute::Metrics(metrics_to_netlink(&route.metrics)));
+        }
+
+        if route.destination.is_ipv4() && (route.preference.is_some() || route.expires.is_some()) {
+            return Err(Error::new(
+                io::ErrorKind::InvalidInput,
+                "preference and expiry are only supported for ipv6 routes",
+            ));
+        }
+
+        if let Some(preference) = route.preference {
+            msg.attributes
+                .push(RouteAttribute::Preference(match preference {
+                    RoutePreference::Low => nl_route::RoutePreference::Low,
+                    RoutePreference::Medium => nl_route::RoutePreference::Medium,
+                    RoutePreference::High => nl_route::RoutePreference::High,
+                }));
+        }
+
+        if let Some(expires) = route.expires {
+            let secs = expires.as_secs().try_into().unwrap_or(u32::MAX);
+            msg.attributes.push(RouteAttribute::Expires(secs));
+        }
+
+        if !route.nexthops.is_empty() && !uses_nexthop_id {
+            let nexthops = route
+                .nexthops
+                .iter()
+                .map(|nexthop| {
+                    let mut nh = RouteNextHop::default();
+                    // the kernel stores the weight minus one
+                    nh.hops = (nexthop.weight.clamp(1, 256) - 1) as u8;
+                    nh.interface_index = nexthop.ifindex.unwrap_or(0);
+                    nh.flags = request_flags(nexthop.flags).into();
+                    if let Some(gateway) = nexthop.gateway {
+                        nh.attributes
+                            .push(gateway_attribute(route.destination, gateway)?);
+                    }
+                    if let Some(encap) = &nexthop.encap {
+                        nh.attributes.extend(encap_attributes(encap)?);
+                    }
+                    Ok(nh)
+                })
+                .collect::<io::Result<_>>()?;
+            msg.attributes.push(RouteAttribute::MultiPath(nexthops));
+        }
+
+        Ok(msg)
+    }
+}
+
+/// Clock ticks per second the kernel uses for times it reports to userspace (USER_HZ).
+const USER_HZ: u64 = 100;
+
+pub(super) fn clock_ticks_to_duration(ticks: u32) -> Duration {
+    Duration::from_millis(ticks as u64 * 1000 / USER_HZ)
+}
+
+pub(super) fn duration_to_clock_ticks(duration: Duration) -> u32 {
+    (duration.as_millis() * USER_HZ as u128 / 1000)
+        .try_into()
+        .unwrap_or(u32::MAX)
+}
+
+/// RTA_NH_ID, the nexthop object a route uses. Not known to netlink-packet-route yet.
+const RTA_NH_ID: u16 = 30;
+
+/// RTAX_CC_ALGO, sent as a string rather than the u32 `RouteMetric::CcAlgo` holds.
+const RTAX_CC_ALGO: u16 = 16;
+
+fn metrics_to_netlink(metrics: &RouteMetrics) -> Vec<RouteMetric> {
+    let values = [
+        (metrics.mtu, RouteMetric::Mtu as fn(u32) -> RouteMetric),
+        (metrics.window, RouteMetric::Window),
+        (metrics.rtt, RouteMetric::Rtt),
+        (metrics.rttvar, RouteMetric::RttVar),
+        (metrics.ssthresh, RouteMetric::SsThresh),
+        (metrics.cwnd, RouteMetric::Cwnd),
+        (metrics.advmss, RouteMetric::Advmss),
+        (metrics.reordering, RouteMetric::Reordering),
+        (metrics.hoplimit, RouteMetric::Hoplimit),
+        (metrics.initcwnd, RouteMetric::InitCwnd),
+        (metrics.features, RouteMetric::Features),
+        (metrics.rto_min, RouteMetric::RtoMin),
+        (metrics.initrwnd, RouteMetric::InitRwnd),
+        (metrics.quickack, RouteMetric::QuickAck),
+        (metrics.fastopen_no_cookie, RouteMetric::FastopenNoCookie),
+    ];
+
+    let mut nlas = vec![];
+    if metrics.lock != 0 {
+        nlas.push(RouteMetric::Lock(metrics.lock));
+    }
+    nlas.extend(
+        values
+            .into_iter()
+            .filter_map(|(value, metric)| value.map(metric)),
+    );
+    if let Some(congctl) = &metrics.congctl {
+        let mut name = congctl.as_bytes().to_vec();
+        name.push(0);
+        nlas.push(RouteMetric::Other(DefaultNla::new(RTAX_CC_ALGO, name)));
+    }
+    nlas
+}
+
+impl From<Vec<RouteMetric>> for RouteMetrics {
+    fn from(nlas: Vec<RouteMetric>) -> Self {
+        let mut metrics = RouteMetrics::default();
+        for nla in nlas {
+            match nla {
+                RouteMetric::Lock(v) => metrics.lock = v,
+                RouteMetric::Mtu(v) => metrics.mtu = Some(v),
+                RouteMetric::Window(v) => metrics.window = Some(v),
+                RouteMetric::Rtt(v) => metrics.rtt = Some(v),
+                RouteMetric::RttVar(v) => metrics.rttvar = Some(v),
+                RouteMetric::SsThresh(v) => metrics.ssthresh = Some(v),
+                RouteMetric::Cwnd(v) => metrics.cwnd = Some(v),
+                RouteMetric::Advmss(v) => metrics.advmss = Some(v),
+                RouteMetric::Reordering(v) => metrics.reordering = Some(v),
+                RouteMetric::Hoplimit(v) => metrics.hoplimit = Some(v),
+                RouteMetric::InitCwnd(v) => metrics.initcwnd = Some(v),
+                RouteMetric::Features(v) => metrics.features = Some(v),
+                RouteMetric::RtoMin(v) => metrics.rto_min = Some(v),
+                RouteMetric::InitRwnd(v) => metrics.initrwnd = Some(v),
+                RouteMetric::QuickAck(v) => metrics.quickack = Some(v),
+                RouteMetric::FastopenNoCookie(v) => metrics.fastopen_no_cookie = Some(v),
+                // RTAX_CC_ALGO, renamed by `RouteSocket` so that it isn't parsed as a u32
+                RouteMetric::Other(nla) if nla.kind() == RTAX_CC_ALGO_NAME => {
+                    let mut name = vec![0; nla.value_len()];
+                    nla.emit_value(&mut name);
+                    metrics.congctl = Some(cc_algo_name(&name));
+                }
+                _ => {}
+            }
+        }
+        metrics
+    }
+}
+
+fn cc_algo_name(name: &[u8]) -> String {
+    let len = name.iter().position(|b| *b == 0).unwrap_or(name.len());
+    String::from_utf8_lossy(&name[..len]).into_owned()
+}
+
+const NEXTHOP_FLAGS: [RouteNextHopFlag; 7] = [
+    RouteNextHopFlag::Dead,
+    RouteNextHopFlag::Pervasive,
+    RouteNextHopFlag::Onlink,
+    RouteNextHopFlag::Offload,
+    RouteNextHopFlag::Linkdown,
+    RouteNextHopFlag::Unresolved,
+    RouteNextHopFlag::Trap,
+];
+
+const ROUTE_FLAGS: [RouteFlag; 16] = [
+    RouteFlag::Dead,
+    RouteFlag::Pervasive,
+    RouteFlag::Onlink,
+    RouteFlag::Offload,
+    RouteFlag::Linkdown,
+    RouteFlag::Unresolved,
+    RouteFlag::Trap,
+    RouteFlag::Notify,
+    RouteFlag::Cloned,
+    RouteFlag::Equalize,
+    RouteFlag::Prefix,
+    RouteFlag::LookupTable,
+    RouteFlag::FibMatch,
+    RouteFlag::RtOffload,
+    RouteFlag::RtTrap,
+    RouteFlag::OffloadFailed,
+];
+
+/// `RTNH_F_*` bits, the part of the route flags which applies to next hops.
+pub(super) const NEXTHOP_FLAGS_MASK: u32 = 0xff;
+
+/// The flags of `flags` which userspace may set when adding a route. The others are reported by
+/// the kernel, which rejects some of them, e.g. DEAD and LINKDOWN for ipv4 routes.
+fn request_flags(flags: RouteFlags) -> RouteFlags {
+    RouteFlags::from_bits(flags.bits() & (RouteFlags::ONLINK | RouteFlags::PERVASIVE).bits())
+}
+
+impl From<RouteFlags> for Vec<RouteFlag> {
+    fn from(flags: RouteFlags) -> Self {
+        let bits = flags.bits();
+        let mut nl_flags: Vec<_> = ROUTE_FLAGS
+            .into_iter()
+            .filter(|flag| bits & u32::from(*flag) != 0)
+            .collect();
+        let rest = bits & !RouteFlags::from(nl_flags.as_slice()).bits();
+        if rest != 0 {
+            nl_flags.push(RouteFlag::Other(rest));
+        }
+        nl_flags
+    }
+}
+
+impl From<&[RouteFlag]> for RouteFlags {
+    fn from(flags: &[RouteFlag]) -> Self {
+        Self::from_bits(flags.iter().fold(0, |bits, flag| bits | u32::from(*flag)))
+    }
+}
+
+impl From<RouteFlags> for Vec<RouteNextHopFlag> {
+    fn from(flags: RouteFlags) -> Self {
+        let bits = (flags.bits() & NEXTHOP_FLAGS_MASK) as u8;
+        let mut nl_flags: Vec<_> = NEXTHOP_FLAGS
+            .into_iter()
+            .filter(|flag| bits & u8::from(*flag) != 0)
+            .collect();
+        let rest = bits & !(RouteFlags::from(nl_flags.as_slice()).bits() as u8);
+        if rest != 0 {
+            nl_flags.push(RouteNextHopFlag::Other(rest));
+        }
+        nl_flags
+    }
+}
+
+impl From<&[RouteNextHopFlag]> for RouteFlags {
+    fn from(flags: &[RouteNextHopFlag]) -> Self {
+        Self::from_bits(
+            flags
+                .iter()
+                .fold(0, |bits, flag| bits | u8::from(*flag) as u32),
+        )
+    }
+}
+
+impl From<RouteNextHop> for NextHop {
+    fn from(nh: RouteNextHop) -> Self {
+        let mut gateway = None;
+        let mut encap_type = RouteLwEnCapType::None;
+        let mut encap = vec![];
+        for attr in nh.attributes {
+            match attr {
+                RouteAttribute::Gateway(addr) => gateway = addr_to_ip(addr),
+                RouteAttribute::Via(via) => gateway = via_to_ip(via),
+                RouteAttribute::EncapType(kind) => encap_type = kind,
+                RouteAttribute::Encap(nlas) => encap = nlas,
+                _ => {}
+            }
+        }
+        Self {
+            gateway,
+            ifindex: Some(nh.interface_index).filter(|i| *i != 0),
+            weight: nh.hops as u16 + 1,
+            flags: nh.flags.as_slice().into(),
+            encap: encap_from_netlink(encap_type, &encap),
+        }
+    }
+}
+
+/// The kernel may report each next hop of an IPv6 multipath route as a separate route sharing
+/// the same key. Fold those back into a single route so they look like IPv4 multipath routes.
+fn merge_multipath(routes: Vec<Route>) -> Vec<Route> {
+    merge_multipath_with(routes.into_iter().map(|route| (route, ())).collect())
+        .into_iter()
+        .map(|(route, ())| route)
+        .collect()
+}
+
+/// Like [`merge_multipath`], for routes carrying a value along. A merged route keeps the value
+/// of its first next hop.
+fn merge_multipath_with<T>(routes: Vec<(Route, T)>) -> Vec<(Route, T)> {
+    let mut merged: Vec<(Route, T)> = Vec::with_capacity(routes.len());
+    let mut siblings = HashMap::new();
+
+    for (route, value) in routes {
+        let Some(key) = sibling_key(&route) else {
+            merged.push((route, value));
+            continue;
+        };
+        match siblings.entry(key) {
+            Entry::Vacant(entry) => {
+                entry.insert(merged.len());
+                merged.push((route, value));
+            }
+            Entry::Occupied(entry) => merge_sibling(&mut merged[*entry.get()].0, route),
+        }
+    }
+
+    merged
+}
+
+/// The result of [`Handle::delete_matching`], given the routes it removed and those it failed
+/// to. Both are merged into multipath routes the way [`Handle::list`] reports them, the next
+/// hops it failed to remove apart from the removed ones.
+fn deletion_results(
+    deleted: Vec<Route>,
+    failed: Vec<(Route, io::Error)>,
+) -> Vec<(Route, io::Result<()>)> {
+    merge_multipath(deleted)
+        .into_iter()
+        .map(|route| (route, Ok(())))
+        .chain(
+            merge_multipath_with(failed)
+                .into_iter()
+                .map(|(route, e)| (route, Err(e))),
+        )
+        .collect()
+}
+
+type SiblingKey = (IpAddr, u8, Option<IpAddr>, u8, u32, Option<u32>, RouteType);
+
+/// The key shared by the next hops of an IPv6 multipath route, `None` if the route can't be
+/// part of one.
+fn sibling_key(route: &Route) -> Option<SiblingKey> {
+    // only gateway routes qualify for IPv6 ECMP
+    if !route.destination.is_ipv6() || (route.gateway.is_none() && route.nexthops.is_empty()) {
+        return None;
+    }
+    Some((
+        route.destination,
+        route.prefix,
+        route.source,
+        route.source_prefix,
+        route.table,
+        route.metric,
+        route.kind,
+    ))
+}
+
+/// Add the next hops of `route` to `first`, turning `first` into a multipath route if needed.
+fn merge_sibling(first: &mut Route, route: Route) {
+    if first.nexthops.is_empty() {
+        let nexthop = NextHop {
+            gateway: first.gateway.take(),
+            ifindex: first.ifindex.take(),
+            encap: first.encap.take(),
+            flags: RouteFlags::from_bits(first.flags.bits() & NEXTHOP_FLAGS_MASK),
+            ..NextHop::new()
+        };
+        first.nexthops.push(nexthop);
+    }
+    // next hops appended later may be reported in a separate multipath route
+    if route.nexthops.is_empty() {
+        first.nexthops.push(NextHop {
+            gateway: route.gateway,
+            ifindex: route.ifindex,
+            encap: route.encap,
+            flags: RouteFlags::from_bits(route.flags.bits() & NEXTHOP_FLAGS_MASK),
+            ..NextHop::new()
+        });
+    } else {
+        first.nexthops.extend(route.nexthops);
+    }
+}
+
+/// Scope to install `route` with, picking one like `ip route` does if none was given.
+fn route_scope(route: &Route) -> RouteScope {
+    route.scope.unwrap_or(match route.kind {
+        RouteType::Local | RouteType::Nat => RouteScope::Host,
+        RouteType::Broadcast | RouteType::Multicast | RouteType::Anycast => RouteScope::Link,
+        RouteType::Unicast | RouteType::Unspec
+            if route.gateway.is_none()
+                && route.nexthops.is_empty()
+                && route.nexthop_id.is_none() =>
+        {
+            RouteScope::Link
+        }
+        _ => RouteScope::Universe,
+    })
+}
+
+impl From<nl_route::RouteType> for RouteType {
+    fn from(kind: nl_route::RouteType) -> Self {
+        match kind {
+            nl_route::RouteType::Unspec => Self::Unspec,
+            nl_route::RouteType::Unicast => Self::Unicast,
+            nl_route::RouteType::Local => Self::Local,
+            nl_route::RouteType::Broadcast => Self::Broadcast,
+            nl_route::RouteType::Anycast => Self::Anycast,
+            nl_route::RouteType::Multicast => Self::Multicast,
+            nl_route::RouteType::BlackHole => Self::Blackhole,
+            nl_route::RouteType::Unreachable => Self::Unreachable,
+            nl_route::RouteType::Prohibit => Self::Prohibit,
+            nl_route::RouteType::Throw => Self::Throw,
+            nl_route::RouteType::Nat => Self::Nat,
+            nl_route::RouteType::ExternalResolve => Self::ExternalResolve,
+            other => Self::Other(other.into()),
+        }
+    }
+}
+
+impl From<RouteType> for nl_route::RouteType {
+    fn from(kind: RouteType) -> Self {
+        match kind {
+            RouteType::Unspec => Self::Unspec,
+            RouteType::Unicast => Self::Unicast,
+            RouteType::Local => Self::Local,
+            RouteType::Broadcast => Self::Broadcast,
+            RouteType::Anycast => Self::Anycast,
+            RouteType::Multicast => Self::Multicast,
+            RouteType::Blackhole => Self::BlackHole,
+            RouteType::Unreachable => Self::Unreachable,
+            RouteType::Prohibit => Self::Prohibit,
+            RouteType::Throw => Self::Throw,
+            RouteType::Nat => Self::Nat,
+            RouteType::ExternalResolve => Self::ExternalResolve,
+            RouteType::Other(kind) => kind.into(),
+        }
+    }
+}
+
+impl From<nl_route::RouteProtocol> for RouteProtocol {
+    fn from(protocol: nl_route::RouteProtocol) -> Self {
+        match protocol {
+            nl_route::RouteProtocol::Unspec => Self::Unspec,
+            nl_route::RouteProtocol::IcmpRedirect => Self::Redirect,
+            nl_route::RouteProtocol::Kernel => Self::Kernel,
+            nl_route::RouteProtocol::Boot => Self::Boot,
+            nl_route::RouteProtocol::Static => Self::Static,
+            nl_route::RouteProtocol::Gated => Self::Gated,
+            nl_route::RouteProtocol::Ra => Self::Ra,
+            nl_route::RouteProtocol::Mrt => Self::Mrt,
+            nl_route::RouteProtocol::Zebra => Self::Zebra,
+            nl_route::RouteProtocol::Bird => Self::Bird,
+            nl_route::RouteProtocol::DnRouted => Self::DnRouted,
+            nl_route::RouteProtocol::Xorp => Self::Xorp,
+            nl_route::RouteProtocol::Ntk => Self::Ntk,
+            nl_route::RouteProtocol::Dhcp => Self::Dhcp,
+            nl_route::RouteProtocol::Mrouted => Self::Mrouted,
+            nl_route::RouteProtocol::KeepAlived => Self::Keepalived,
+            nl_route::RouteProtocol::Babel => Self::Babel,
+            nl_route::RouteProtocol::Bgp => Self::Bgp,
+            nl_route::RouteProtocol::Isis => Self::Isis,
+            nl_route::RouteProtocol::Ospf => Self::Ospf,
+            nl_route::RouteProtocol::Rip => Self::Rip,
+            nl_route::RouteProtocol::Eigrp => Self::Eigrp,
+            other => Self::Other(other.into()),
+        }
+    }
+}
+
+impl From<RouteProtocol> for nl_route::RouteProtocol {
+    fn from(protocol: RouteProtocol) -> Self {
+        match protocol {
+            RouteProtocol::Unspec => Self::Unspec,
+            RouteProtocol::Redirect => Self::IcmpRedirect,
+            RouteProtocol::Kernel => Self::Kernel,
+            RouteProtocol::Boot => Self::Boot,
+            RouteProtocol::Static => Self::Static,
+            RouteProtocol::Gated => Self::Gated,
+            RouteProtocol::Ra => Self::Ra,
+            RouteProtocol::Mrt => Self::Mrt,
+            RouteProtocol::Zebra => Self::Zebra,
+            RouteProtocol::Bird => Self::Bird,
+            RouteProtocol::DnRouted => Self::DnRouted,
+            RouteProtocol::Xorp => Self::Xorp,
+            RouteProtocol::Ntk => Self::Ntk,
+            RouteProtocol::Dhcp => Self::Dhcp,
+            RouteProtocol::Mrouted => Self::Mrouted,
+            RouteProtocol::Keepalived => Self::KeepAlived,
+            RouteProtocol::Babel => Self::Babel,
+            RouteProtocol::Bgp => Self::Bgp,
+            RouteProtocol::Isis => Self::Isis,
+            RouteProtocol::Ospf => Self::Ospf,
+            RouteProtocol::Rip => Self::Rip,
+            RouteProtocol::Eigrp => Self::Eigrp,
+            RouteProtocol::Other(protocol) => protocol.into(),
+        }
+    }
+}
+
+impl From<nl_route::RouteScope> for RouteScope {
+    fn from(scope: nl_route::RouteScope) -> Self {
+        match scope {
+            nl_route::RouteScope::Universe => Self::Universe,
+            nl_route::RouteScope::Site => Self::Site,
+            nl_route::RouteScope::Link => Self::Link,
+            nl_route::RouteScope::Host => Self::Host,
+            nl_route::RouteScope::NoWhere => Self::Nowhere,
+            other => Self::Other(other.into()),
+        }
+    }
+}
+
+impl From<RouteScope> for nl_route::RouteScope {
+    fn from(scope: RouteScope) -> Self {
+        match scope {
+            RouteScope::Universe => Self::Universe,
+            RouteScope::Site => Self::Site,
+            RouteScope::Link => Self::Link,
+            RouteScope::Host => Self::Host,
+            RouteScope::Nowhere => Self::NoWhere,
+            RouteScope::Other(scope) => scope.into(),
+        }
+    }
+}
+
+trait RouteExt {
+    fn destination_prefix(&self) -> Option<(IpAddr, u8)>;
+}
+
+impl RouteExt for RouteMessage {
+    fn destination_prefix(&self) -> Option<(IpAddr, u8)> {
+        self.attributes
+            .iter()
+            .flat_map(|attr| {
+                if let RouteAttribute::Destination(addr) = attr {
+                    addr_to_ip(addr.clone())
+                        .map(|addr| (addr, self.header.destination_prefix_length))
+                } else {
+                    None
+                }
+            })
+            .next()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::io;
+    use std::net::IpAddr;
+    use std::num::NonZeroI32;
+    use std::time::Duration;
+
+    use netlink_packet_route::{
+        route::{self as nl_route, RouteAddress, RouteAttribute, RouteMessage, RouteMessageBuffer},
+        AddressFamily,
+    };
+    use netlink_packet_utils::{nla::DefaultNla, Emitable, Parseable};
+
+    use netlink_packet_core::{
+        ErrorMessage, NetlinkMessage, NetlinkPayload, NLM_F_APPEND, NLM_F_CREATE, NLM_F_REPLACE,
+    };
+    use netlink_packet_route::RouteNetlinkMessage;
+
+    use super::super::socket::rename_cc_algo;
+    use super::{
+        delete_message, deletion_results, is_empty_dump_error, merge_multipath, new_route_change,
+        pipelined, route_scope, set_dump_filter,
+    };
+    use crate::{
+        Encap, IpVersion, NextHop, Route, RouteChange, RouteFilter, RouteFlags, RouteMetrics,
+        RoutePreference, RouteProtocol, RouteRealm, RouteScope, RouteType,
+    };
+
+    #[test]
+    fn it_reads_table_id_from_attribute() {
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = AddressFamily::Inet;
+        msg.header.destination_prefix_length = 24;
+        // RT_TABLE_COMPAT, which is what the kernel reports in the header for tables > 255
+        msg.header.table = 252;
+        msg.attributes
+            .push(RouteAttribute::Destination(RouteAddress::Inet(
+                "10.0.0.0".parse().unwrap(),
+            )));
+        msg.attributes.push(RouteAttribute::Table(1001));
+
+        let route: Route = msg.into();
+        assert_eq!(route.table, 1001);
+        assert_eq!(route.prefix, 24);
+    }
+
+    #[test]
+    fn it_reads_route_type() {
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = AddressFamily::Inet6;
+        msg.header.kind = nl_route::RouteType::BlackHole;
+
+        let route: Route = msg.into();
+        assert_eq!(route.kind, RouteType::Blackhole);
+        assert_eq!(
+            nl_route::RouteType::from(RouteType::Other(42)),
+            nl_route::RouteType::Other(42)
+        );
+    }
+
+    #[test]
+    fn it_reads_route_protocol() {
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = AddressFamily::Inet;
+        msg.header.protocol = nl_route::RouteProtocol::Other(99);
+
+        let route: Route = msg.into();
+        assert_eq!(route.protocol, RouteProtocol::Other(99));
+        assert_eq!(
+            nl_route::RouteProtocol::from(RouteProtocol::Dhcp),
+            nl_route::RouteProtocol::Dhcp
+        );
+    }
+
+    #[test]
+    fn it_picks_default_scope() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8);
+        assert_eq!(route_scope(&route), RouteScope::Link);
+
+        let route = route.with_gateway("192.168.1.1".parse().unwrap());
+        assert_eq!(route_scope(&route), RouteScope::Universe);
+
+        let route = route.with_kind(RouteType::Local);
+        assert_eq!(route_scope(&route), RouteScope::Host);
+
+        let route = route.with_scope(RouteScope::Site);
+        assert_eq!(route_scope(&route), RouteScope::Site);
+    }
+
+    #[test]
+    fn it_round_trips_multipath_routes() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .with_nexthop(
+                NextHop::new()
+                    .with_gateway("192.168.1.1".parse().unwrap())
+                    .with_ifindex(2),
+            )
+            .with_nexthop(
+                NextHop::new()
+                    .with_gateway("192.168.2.1".parse().unwrap())
+                    .with_ifindex(3)
+                    .with_weight(10),
+            )
+            .with_scope(RouteScope::Universe);
+
+        let msg = RouteMessage::try_from(&route).unwrap();
+        assert_eq!(Route::from(msg), route);
+    }
+
+    #[test]
+    fn it_rejects_multipath_gateway_of_wrong_family() {
+        let route = Route::new("2001:db8::".parse().unwrap(), 32)
+            .with_nexthop(NextHop::new().with_gateway("192.168.1.1".parse().unwrap()));
+        assert!(RouteMessage::try_from(&route).is_err());
+    }
+
+    #[test]
+    fn it_merges_v6_multipath_routes() {
+        let a = Route::new("2001:db8::".parse().unwrap(), 32)
+            .with_gateway("fe80::1".parse().unwrap())
+            .with_ifindex(2)
+            .with_metric(1024);
+        let b = Route::new("2001:db8::".parse().unwrap(), 32)
+            .with_gateway("fe80::2".parse().unwrap())
+            .with_ifindex(3)
+            .with_metric(1024);
+        let other = Route::new("2001:db8::".parse().unwrap(), 32)
+            .with_gateway("fe80::3".parse().unwrap())
+            .with_metric(2048);
+
+        let merged = merge_multipath(vec![a, other.clone(), b]);
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].gateway, None);
+        assert_eq!(
+            merged[0].nexthops,
+            vec![
+                NextHop::new()
+                    .with_gateway("fe80::1".parse().unwrap())
+                    .with_ifindex(2),
+                NextHop::new()
+                    .with_gateway("fe80::2".parse().unwrap())
+                    .with_ifindex(3),
+            ]
+        );
+        assert_eq!(merged[1], other);
+    }
+
+    #[test]
+    fn it_merges_appended_next_hops_into_multipath_routes() {
+        let a = Route::new("2001:db8::".parse().unwrap(), 32)
+            .with_nexthop(NextHop::new().with_gateway("fe80::1".parse().unwrap()))
+            .with_nexthop(NextHop::new().with_gateway("fe80::2".parse().unwrap()));
+        let b = Route::new("2001:db8::".parse().unwrap(), 32)
+            .with_gateway("fe80::3".parse().unwrap())
+            .with_ifindex(3);
+
+        let merged = merge_multipath(vec![a, b]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(
+            merged[0]
+                .nexthops
+                .iter()
+                .map(|nh| nh.gateway.unwrap())
+                .collect::<Vec<_>>(),
+            vec![
+                "fe80::1".parse::<IpAddr>().unwrap(),
+                "fe80::2".parse().unwrap(),
+                "fe80::3".parse().unwrap()
+            ]
+        );
+    }
+
+    #[test]
+    fn it_merges_deleted_and_failed_routes_alike() {
+        let sibling = |gateway: &str| {
+            Route::new("2001:db8::".parse().unwrap(), 32)
+                .with_gateway(gateway.parse().unwrap())
+                .with_metric(1024)
+        };
+        // EBUSY
+        let failure = || io::Error::from_raw_os_error(16);
+
+        let results = deletion_results(
+            vec![sibling("fe80::1"), sibling("fe80::2")],
+            vec![
+                (sibling("fe80::3"), failure()),
+                (sibling("fe80::4"), failure()),
+            ],
+        );
+        assert_eq!(results.len(), 2);
+        let gateways = |route: &Route| {
+            route
+                .nexthops
+                .iter()
+                .map(|nh| nh.gateway.unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        assert!(results[0].1.is_ok());
+        assert_eq!(gateways(&results[0].0), ["fe80::1", "fe80::2"]);
+        assert_eq!(results[1].1.as_ref().unwrap_err().raw_os_error(), Some(16));
+        assert_eq!(gateways(&results[1].0), ["fe80::3", "fe80::4"]);
+    }
+
+    #[test]
+    fn it_round_trips_nexthop_id() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8).with_nexthop_id(42);
+        assert_eq!(route_scope(&route), RouteScope::Universe);
+
+        let msg = RouteMessage::try_from(&route).unwrap();
+        assert_eq!(Route::from(msg).nexthop_id, Some(42));
+
+        // listed routes carry the next hop of the object too
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = AddressFamily::Inet;
+        msg.attributes = vec![
+            RouteAttribute::Other(DefaultNla::new(30, 5u32.to_ne_bytes().to_vec())),
+            RouteAttribute::Gateway(RouteAddress::Inet("192.168.1.1".parse().unwrap())),
+            RouteAttribute::Oif(2),
+        ];
+        let route = Route::from(msg);
+        assert_eq!(route.nexthop_id, Some(5));
+        assert_eq!(route.ifindex, Some(2));
+
+        let msg = RouteMessage::try_from(&route).unwrap();
+        assert!(!msg.attributes.iter().any(|attr| matches!(
+            attr,
+            RouteAttribute::Gateway(_) | RouteAttribute::Oif(_) | RouteAttribute::MultiPath(_)
+        )));
+    }
+
+    #[test]
+    fn it_matches_the_full_route_key() {
+        let listed = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .with_gateway("192.168.1.1".parse().unwrap())
+            .with_ifindex(2)
+            .with_table(100);
+
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8).with_table(100);
+        assert!(route.matches_key(&listed));
+        assert!(route.clone().with_metric(0).matches_key(&listed));
+        assert!(route
+            .clone()
+            .with_gateway("192.168.1.1".parse().unwrap())
+            .matches_key(&listed));
+        assert!(!route
+            .clone()
+            .with_gateway("192.168.1.2".parse().unwrap())
+            .matches_key(&listed));
+        assert!(!route.clone().with_ifindex(3).matches_key(&listed));
+        assert!(!route.clone().with_tos(0x10).matches_key(&listed));
+        assert!(!route.with_table(254).matches_key(&listed));
+    }
+
+    #[test]
+    fn it_builds_delete_messages_from_the_key() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .with_table(1000)
+            .with_tos(0x10)
+            .with_metric(20)
+            .with_kind(RouteType::Blackhole)
+            .with_protocol(RouteProtocol::Boot);
+        let msg = delete_message(&route).unwrap();
+        assert_eq!(msg.header.tos, 0x10);
+        assert_eq!(msg.header.kind, nl_route::RouteType::Unspec);
+        assert_eq!(msg.header.protocol, nl_route::RouteProtocol::Unspec);
+        assert_eq!(msg.header.scope, nl_route::RouteScope::NoWhere);
+        assert_eq!(
+            msg.attributes,
+            vec![
+                RouteAttribute::Table(1000),
+                RouteAttribute::Destination(RouteAddress::Inet("10.0.0.0".parse().unwrap())),
+                RouteAttribute::Priority(20),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_filters_routes() {
+        let route = Route::new("10.1.2.0".parse().unwrap(), 24)
+            .with_ifindex(2)
+            .with_table(100);
+
+        assert!(RouteFilter::new()
+            .with_family(IpVersion::V4)
+            .with_destination("10.0.0.0".parse().unwrap(), 8)
+            .with_table(100)
+            .with_ifindex(2)
+            .matches(&route));
+        assert!(!RouteFilter::new()
+            .with_family(IpVersion::V6)
+            .matches(&route));
+        assert!(!RouteFilter::new()
+            .with_destination("10.1.2.0".parse().unwrap(), 25)
+            .matches(&route));
+        assert!(!RouteFilter::new()
+            .with_destination("10.2.0.0".parse().unwrap(), 16)
+            .matches(&route));
+        assert!(!RouteFilter::new()
+            .with_destination("::".parse().unwrap(), 0)
+            .matches(&route));
+
+        // delete_matching refuses filters which would remove the local routes too
+        assert!(RouteFilter::new().is_unrestricted());
+        assert!(RouteFilter::new()
+            .with_family(IpVersion::V4)
+            .is_unrestricted());
+        assert!(!RouteFilter::new().with_table(100).is_unrestricted());
+
+        let mut msg = RouteMessage::default();
+        set_dump_filter(
+            &mut msg,
+            &RouteFilter::new()
+                .with_table(1000)
+                .with_protocol(RouteProtocol::Bgp)
+                .with_ifindex(2),
+        );
+        assert_eq!(msg.header.table, 0);
+        assert_eq!(msg.header.protocol, nl_route::RouteProtocol::Bgp);
+        assert_eq!(
+            msg.attributes,
+            vec![RouteAttribute::Table(1000), RouteAttribute::Oif(2)]
+        );
+    }
+
+    #[tokio::test]
+    async fn it_pipelines_requests_in_order() {
+        let in_flight = Cell::new(0);
+        let max_in_flight = Cell::new(0);
+        let op = |n: &usize| {
+            let n = *n;
+            let (in_flight, max_in_flight) = (&in_flight, &max_in_flight);
+            async move {
+                in_flight.set(in_flight.get() + 1);
+                max_in_flight.set(max_in_flight.get().max(in_flight.get()));
+                // complete out of order
+                for _ in 0..=n {
+                    tokio::task::yield_now().await;
+                }
+                in_flight.set(in_flight.get() - 1);
+                match n {
+                    0 => Ok(()),
+                    n => Err(io::Error::other(n.to_string())),
+                }
+            }
+        };
+        let items = [3, 0, 2, 1];
+        let describe = |results: Vec<io::Result<()>>| {
+            results
+                .into_iter()
+                .map(|res| res.err().map(|e| e.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let expected = vec![
+            Some("3".to_string()),
+            None,
+            Some("2".to_string()),
+            Some("1".to_string()),
+        ];
+
+        assert_eq!(describe(pipelined(&items, 4, op).await), expected);
+        assert_eq!(max_in_flight.get(), 4);
+
+        max_in_flight.set(0);
+        assert_eq!(describe(pipelined(&items, 0, op).await), expected);
+        assert_eq!(max_in_flight.get(), 1);
+    }
+
+    #[test]
+    fn it_treats_missing_filter_targets_as_empty_dumps() {
+        let error = |code| {
+            let mut msg = ErrorMessage::default();
+            msg.code = NonZeroI32::new(code);
+            rtnetlink::Error::NetlinkError(msg)
+        };
+        // ENOENT for a table, ENODEV for an interface the family doesn't have
+        assert!(is_empty_dump_error(&error(-2)));
+        assert!(is_empty_dump_error(&error(-19)));
+        // EINVAL, e.g. for a filter the kernel rejects
+        assert!(!is_empty_dump_error(&error(-22)));
+        assert!(!is_empty_dump_error(&rtnetlink::Error::RequestFailed));
+    }
+
+    #[test]
+    fn it_reports_replaced_routes_as_changes() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8);
+        assert_eq!(
+            new_route_change(NLM_F_CREATE, route.clone()),
+            RouteChange::Add(route.clone())
+        );
+        assert_eq!(
+            new_route_change(NLM_F_REPLACE, route.clone()),
+            RouteChange::Change(route.clone())
+        );
+        assert_eq!(
+            new_route_change(NLM_F_CREATE | NLM_F_APPEND, route.clone()),
+            RouteChange::Change(route)
+        );
+    }
+
+    #[test]
+    fn it_round_trips_tos_and_realm() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .with_ifindex(2)
+            .with_tos(0x10)
+            .with_realm(RouteRealm::new(1, 2))
+            .with_scope(RouteScope::Link);
+        let msg = RouteMessage::try_from(&route).unwrap();
+        assert_eq!(msg.header.tos, 0x10);
+        let parsed = Route::from(msg);
+        assert_eq!(parsed, route);
+
+        assert!(RouteFilter::new().with_tos(0x10).matches(&parsed));
+        assert!(!RouteFilter::new().with_tos(0x08).matches(&parsed));
+        assert!(!RouteFilter::new()
+            .with_realm(RouteRealm::new(0, 2))
+            .matches(&parsed));
+    }
+
+    #[test]
+    fn it_round_trips_mpls_encap() {
+        let encap = Encap::Mpls {
+            labels: vec![100, 200],
+            ttl: Some(64),
+        };
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .with_gateway("192.168.1.1".parse().unwrap())
+            .with_encap(encap.clone())
+            .with_scope(RouteScope::Universe);
+        let msg = RouteMessage::try_from(&route).unwrap();
+        assert_eq!(Route::from(msg), route);
+
+        // parse from the wire, where only the next hop carries an encap type
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8).with_nexthop(
+            NextHop::new()
+                .with_gateway("192.168.1.1".parse().unwrap())
+                .with_encap(Encap::Mpls {
+                    labels: vec![300],
+                    ttl: None,
+                }),
+        );
+        let msg = RouteMessage::try_from(&route).unwrap();
+        let mut buf = vec![0; msg.buffer_len()];
+        msg.emit(&mut buf);
+        let msg = RouteMessage::parse(&RouteMessageBuffer::new(&buf)).unwrap();
+        assert_eq!(Route::from(msg).nexthops, route.nexthops);
+
+        let route = route.with_encap(Encap::Mpls {
+            labels: vec![1 << 20],
+            ttl: None,
+        });
+        assert!(RouteMessage::try_from(&route).is_err());
+    }
+
+    #[test]
+    fn it_round_trips_metrics() {
+        for congctl in ["bbr", "cubic"] {
+            let metrics = RouteMetrics {
+                lock: RouteMetrics::LOCK_MTU,
+                mtu: Some(1400),
+                initcwnd: Some(30),
+                congctl: Some(congctl.to_string()),
+                ..Default::default()
+            };
+            let route = Route::new("10.0.0.0".parse().unwrap(), 8)
+                .with_ifindex(2)
+                .with_metrics(metrics.clone());
+
+            // the kernel reports congctl the same way we send it, as a NUL terminated string
+            let mut req = NetlinkMessage::from(RouteNetlinkMessage::NewRoute(
+                RouteMessage::try_from(&route).unwrap(),
+            ));
+            req.finalize();
+            let mut buf = vec![0; req.buffer_len()];
+            req.serialize(&mut buf);
+
+            // netlink-packet-route can't parse names of more than 3 bytes by itself
+            let parsed = NetlinkMessage::<RouteNetlinkMessage>::deserialize(&buf);
+            assert_eq!(parsed.is_ok(), congctl.len() <= 3);
+
+            rename_cc_algo(&mut buf);
+            let NetlinkPayload::InnerMessage(RouteNetlinkMessage::NewRoute(msg)) =
+                NetlinkMessage::<RouteNetlinkMessage>::deserialize(&buf)
+                    .unwrap()
+                    .payload
+            else {
+                panic!("not a route message");
+            };
+            assert_eq!(Route::from(msg).metrics, metrics);
+        }
+    }
+
+    #[test]
+    fn it_converts_route_flags() {
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = AddressFamily::Inet;
+        msg.header.flags = vec![
+            nl_route::RouteFlag::Linkdown,
+            nl_route::RouteFlag::RtOffload,
+        ];
+        let route: Route = msg.into();
+        assert!(route
+            .flags
+            .contains(RouteFlags::LINKDOWN | RouteFlags::ROUTE_OFFLOAD));
+        assert!(!route.flags.contains(RouteFlags::DEAD));
+
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .with_gateway("192.168.1.1".parse().unwrap())
+            .with_flags(RouteFlags::ONLINK)
+            .with_nexthop(NextHop::new().with_flags(RouteFlags::from_bits(0x80)));
+        let msg = RouteMessage::try_from(&route).unwrap();
+        assert_eq!(msg.header.flags, vec![nl_route::RouteFlag::Onlink]);
+        // unknown flags aren't sent back to the kernel
+        assert!(Route::from(msg).nexthops[0].flags.is_empty());
+
+        // a listed route of a link which is down
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = AddressFamily::Inet;
+        msg.header.flags = vec![nl_route::RouteFlag::Linkdown, nl_route::RouteFlag::Dead];
+        let mut nh = nl_route::RouteNextHop::default();
+        nh.flags = vec![
+            nl_route::RouteNextHopFlag::Linkdown,
+            nl_route::RouteNextHopFlag::Onlink,
+        ];
+        nh.interface_index = 3;
+        msg.attributes
+            .push(RouteAttribute::MultiPath(vec![nh.clone(), nh]));
+        let route = Route::from(msg);
+        assert!(route.nexthops[0].flags.contains(RouteFlags::LINKDOWN));
+
+        let msg = RouteMessage::try_from(&route).unwrap();
+        assert!(msg.header.flags.is_empty());
+        let Some(RouteAttribute::MultiPath(hops)) = msg.attributes.last() else {
+            panic!("no multipath attribute");
+        };
+        assert!(hops
+            .iter()
+            .all(|nh| nh.flags == vec![nl_route::RouteNextHopFlag::Onlink]));
+    }
+
+    #[test]
+    fn it_round_trips_preference_and_expiry() {
+        let route = Route::new("2001:db8::".parse().unwrap(), 32)
+            .with_gateway("fe80::1".parse().unwrap())
+            .with_ifindex(2)
+            .with_preference(RoutePreference::High)
+            .with_expires(Duration::from_secs(1800))
+            .with_scope(RouteScope::Universe);
+        let msg = RouteMessage::try_from(&route).unwrap();
+        assert_eq!(Route::from(msg), route);
+
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .with_ifindex(2)
+            .with_preference(RoutePreference::High);
+        assert!(RouteMessage::try_from(&route).is_err());
+    }
+
+    #[test]
+    fn it_uses_via_for_v6_gateways_of_v4_routes() {
+        let route = Route::new("10.0.0.0".parse().unwrap(), 8)
+            .with_gateway("fe80::1".parse().unwrap())
+            .with_ifindex(2)
+            .with_nexthop(NextHop::new().with_gateway("fe80::2".parse().unwrap()));
+        let msg = RouteMessage::try_from(&route).unwrap();
+        assert!(msg
+            .attributes
+            .contains(&RouteAttribute::Via(nl_route::RouteVia::Inet6(
+                "fe80::1".parse().unwrap()
+            ))));
+
+        let parsed = Route::from(msg);
+        assert_eq!(parsed.gateway, route.gateway);
+        assert_eq!(parsed.nexthops, route.nexthops);
+
+        let route = Route::new("2001:db8::".parse().unwrap(), 32)
+            .with_gateway("192.168.1.1".parse().unwrap());
+        assert!(RouteMessage::try_from(&route).is_err());
+    }
+}
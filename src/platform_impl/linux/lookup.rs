@@ -0,0 +1,122 @@
+//! Route lookups (`RTM_GETROUTE` without `NLM_F_DUMP`), like `ip route get`.
+
+use crate::RouteLookup;
+use std::io;
+use std::net::IpAddr;
+
+use netlink_packet_route::{
+    route::{RouteAttribute, RouteFlag, RouteMessage},
+    AddressFamily,
+};
+use netlink_packet_utils::nla::DefaultNla;
+
+use super::linux::{check_family, ip_to_addr};
+
+// not modeled by netlink-packet-route yet
+const RTA_IP_PROTO: u16 = 27;
+const RTA_SPORT: u16 = 28;
+const RTA_DPORT: u16 = 29;
+
+pub(super) fn lookup_message(
+    destination: IpAddr,
+    options: &RouteLookup,
+) -> io::Result<RouteMessage> {
+    let mut msg = RouteMessage::default();
+    let (family, len) = match destination {
+        IpAddr::V4(_) => (AddressFamily::Inet, 32),
+        IpAddr::V6(_) => (AddressFamily::Inet6, 128),
+    };
+    msg.header.address_family = family;
+    msg.header.destination_prefix_length = len;
+    if options.fibmatch {
+        msg.header.flags.push(RouteFlag::FibMatch);
+    }
+
+    msg.attributes
+        .push(RouteAttribute::Destination(ip_to_addr(destination)));
+
+    if let Some(src) = options.source {
+        check_family(destination, src, "source version must match destination")?;
+        msg.header.source_prefix_length = len;
+        msg.attributes.push(RouteAttribute::Source(ip_to_addr(src)));
+    }
+
+    if let Some(iif) = options.iif {
+        msg.attributes.push(RouteAttribute::Iif(iif));
+    }
+
+    if let Some(oif) = options.oif {
+        msg.attributes.push(RouteAttribute::Oif(oif));
+    }
+
+    if let Some(mark) = options.mark {
+        msg.attributes.push(RouteAttribute::Mark(mark));
+    }
+
+    if let Some(uid) = options.uid {
+        msg.attributes.push(RouteAttribute::Uid(uid));
+    }
+
+    if let Some(proto) = options.ip_proto {
+        msg.attributes.push(RouteAttribute::Other(DefaultNla::new(
+            RTA_IP_PROTO,
+            vec![proto],
+        )));
+    }
+
+    // ports are in network byte order
+    if let Some(port) = options.sport {
+        msg.attributes.push(RouteAttribute::Other(DefaultNla::new(
+            RTA_SPORT,
+            port.to_be_bytes().to_vec(),
+        )));
+    }
+
+    if let Some(port) = options.dport {
+        msg.attributes.push(RouteAttribute::Other(DefaultNla::new(
+            RTA_DPORT,
+            port.to_be_bytes().to_vec(),
+        )));
+    }
+
+    Ok(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use netlink_packet_route::route::{RouteAttribute, RouteFlag};
+    use netlink_packet_utils::nla::Nla;
+
+    use super::{lookup_message, RTA_DPORT};
+    use crate::RouteLookup;
+
+    #[test]
+    fn it_builds_lookup_requests() {
+        let options = RouteLookup::new()
+            .with_source("10.0.0.1".parse().unwrap())
+            .with_mark(7)
+            .with_ip_proto(6)
+            .with_dport(443)
+            .with_fibmatch(true);
+        let msg = lookup_message("192.0.2.1".parse().unwrap(), &options).unwrap();
+        assert_eq!(msg.header.destination_prefix_length, 32);
+        assert_eq!(msg.header.source_prefix_length, 32);
+        assert_eq!(msg.header.flags, vec![RouteFlag::FibMatch]);
+        assert!(msg.attributes.contains(&RouteAttribute::Mark(7)));
+
+        let dport = msg
+            .attributes
+            .iter()
+            .find_map(|attr| match attr {
+                RouteAttribute::Other(nla) if nla.kind() == RTA_DPORT => Some(nla),
+                _ => None,
+            })
+            .unwrap();
+        let mut value = [0; 2];
+        dport.emit_value(&mut value);
+        assert_eq!(value, 443u16.to_be_bytes());
+
+        let options = RouteLookup::new().with_source("::1".parse().unwrap());
+        assert!(lookup_message("192.0.2.1".parse().unwrap(), &options).is_err());
+    }
+}
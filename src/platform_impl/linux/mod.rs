@@ -0,0 +1,10 @@
+mod encap;
+#[allow(clippy::module_inception)]
+pub(crate) mod linux;
+mod lookup;
+mod mpls;
+mod multicast;
+mod nexthop;
+mod socket;
+
+pub(crate) use linux::Handle;
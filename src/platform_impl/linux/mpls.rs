@@ -0,0 +1,124 @@
+//! Routes of the MPLS forwarding table (`AF_MPLS`).
+
+use crate::MplsRoute;
+use std::io;
+use std::net::IpAddr;
+
+use netlink_packet_route::{
+    route::{
+        RouteAddress, RouteAttribute, RouteHeader, RouteMessage, RouteMplsTtlPropagation,
+        RouteProtocol, RouteScope, RouteType, RouteVia,
+    },
+    AddressFamily,
+};
+
+use super::encap::mpls_labels;
+
+/// MPLS routes always match a whole label.
+const MPLS_LABEL_LEN: u8 = 20;
+
+impl TryFrom<&MplsRoute> for RouteMessage {
+    type Error = io::Error;
+
+    fn try_from(route: &MplsRoute) -> io::Result<Self> {
+        let mut msg = RouteMessage::default();
+        msg.header.address_family = AddressFamily::Mpls;
+        msg.header.destination_prefix_length = MPLS_LABEL_LEN;
+        msg.header.table = RouteHeader::RT_TABLE_MAIN;
+        msg.header.kind = RouteType::Unicast;
+        msg.header.scope = RouteScope::Universe;
+        msg.header.protocol = RouteProtocol::from(route.protocol);
+
+        let label = mpls_labels(&[route.label])?.remove(0);
+        msg.attributes
+            .push(RouteAttribute::Destination(RouteAddress::Mpls(label)));
+
+        if !route.out_labels.is_empty() {
+            msg.attributes
+                .push(RouteAttribute::NewDestination(mpls_labels(
+                    &route.out_labels,
+                )?));
+        }
+
+        if let Some(via) = route.via {
+            msg.attributes.push(RouteAttribute::Via(match via {
+                IpAddr::V4(addr) => RouteVia::Inet(addr),
+                IpAddr::V6(addr) => RouteVia::Inet6(addr),
+            }));
+        }
+
+        if let Some(ifindex) = route.ifindex {
+            msg.attributes.push(RouteAttribute::Oif(ifindex));
+        }
+
+        if let Some(propagate) = route.ttl_propagate {
+            msg.attributes
+                .push(RouteAttribute::TtlPropagate(if propagate {
+                    RouteMplsTtlPropagation::Enabled
+                } else {
+                    RouteMplsTtlPropagation::Disabled
+                }));
+        }
+
+        Ok(msg)
+    }
+}
+
+impl From<RouteMessage> for MplsRoute {
+    fn from(msg: RouteMessage) -> Self {
+        let mut route = MplsRoute::new(0).with_protocol(msg.header.protocol.into());
+
+        for attr in msg.attributes {
+            match attr {
+                RouteAttribute::Destination(RouteAddress::Mpls(label)) => {
+                    route.label = label.label;
+                }
+                RouteAttribute::NewDestination(labels) => {
+                    route.out_labels = labels.into_iter().map(|label| label.label).collect();
+                }
+                RouteAttribute::Via(RouteVia::Inet(addr)) => route.via = Some(addr.into()),
+                RouteAttribute::Via(RouteVia::Inet6(addr)) => route.via = Some(addr.into()),
+                RouteAttribute::Oif(ifindex) => route.ifindex = Some(ifindex),
+                RouteAttribute::TtlPropagate(propagate) => {
+                    route.ttl_propagate = match propagate {
+                        RouteMplsTtlPropagation::Enabled => Some(true),
+                        RouteMplsTtlPropagation::Disabled => Some(false),
+                        _ => None,
+                    };
+                }
+                _ => {}
+            }
+        }
+        route
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use netlink_packet_route::route::{RouteAddress, RouteAttribute, RouteMessage};
+
+    use crate::{MplsRoute, RouteProtocol};
+
+    #[test]
+    fn it_round_trips_mpls_routes() {
+        let route = MplsRoute::new(100)
+            .with_out_labels(vec![200, 300])
+            .with_via("10.0.0.1".parse().unwrap())
+            .with_ifindex(2)
+            .with_ttl_propagate(false)
+            .with_protocol(RouteProtocol::Bgp);
+        let msg = RouteMessage::try_from(&route).unwrap();
+        assert_eq!(msg.header.destination_prefix_length, 20);
+        assert!(msg.attributes.iter().any(|attr| matches!(
+            attr,
+            RouteAttribute::Destination(RouteAddress::Mpls(label))
+                if label.label == 100 && label.bottom_of_stack
+        )));
+        assert_eq!(MplsRoute::from(msg), route);
+
+        let pop = MplsRoute::new(100).with_ifindex(2);
+        assert_eq!(MplsRoute::from(RouteMessage::try_from(&pop).unwrap()), pop);
+
+        assert!(RouteMessage::try_from(&MplsRoute::new(1 << 20)).is_err());
+    }
+}
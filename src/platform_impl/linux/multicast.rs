@@ -0,0 +1,290 @@
+//! Entries of the multicast forwarding caches (`RTNL_FAMILY_IPMR` and `RTNL_FAMILY_IP6MR`).
+
+use crate::{MulticastOutput, MulticastRoute, MulticastRouteStats};
+use std::io::{self, Error};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use netlink_packet_route::{
+    route::{
+        RouteAddress, RouteAttribute, RouteHeader, RouteMessage, RouteNextHop, RouteProtocol,
+        RouteScope, RouteType,
+    },
+    AddressFamily,
+};
+
+use super::linux::clock_ticks_to_duration;
+
+pub(super) const RTNL_FAMILY_IPMR: u8 = 128;
+pub(super) const RTNL_FAMILY_IP6MR: u8 = 129;
+
+/// The virtual interface table of the default IPv4 multicast table.
+const IP_MR_VIF: &str = "/proc/net/ip_mr_vif";
+
+/// `RT_TABLE_DEFAULT`, the multicast table used without multicast policy routing.
+const RT_TABLE_DEFAULT: u32 = 253;
+
+/// Build the message for adding or deleting `route`.
+///
+/// When adding an entry, the kernel reads the TTLs of the output interfaces from the position of
+/// each next hop, which is the index of the interface in the virtual interface table, rather
+/// than from the interface index it reports in dumps. `vifs` maps interface indexes to those.
+pub(super) fn multicast_route_message(
+    route: &MulticastRoute,
+    vifs: &[u32],
+) -> io::Result<RouteMessage> {
+    if route.source.is_ipv4() != route.group.is_ipv4() {
+        return Err(Error::new(
+            io::ErrorKind::InvalidInput,
+            "source version must match group",
+        ));
+    }
+
+    let mut msg = RouteMessage::default();
+    let len = match route.group {
+        IpAddr::V4(_) => {
+            msg.header.address_family = AddressFamily::Other(RTNL_FAMILY_IPMR);
+            32
+        }
+        IpAddr::V6(_) => {
+            msg.header.address_family = AddressFamily::Other(RTNL_FAMILY_IP6MR);
+            128
+        }
+    };
+    msg.header.destination_prefix_length = len;
+    msg.header.source_prefix_length = len;
+    msg.header.kind = RouteType::Multicast;
+    msg.header.scope = RouteScope::Universe;
+    msg.header.protocol = RouteProtocol::Static;
+    msg.header.table = u8::try_from(route.table).unwrap_or(RouteHeader::RT_TABLE_UNSPEC);
+
+    msg.attributes.push(RouteAttribute::Table(route.table));
+    msg.attributes
+        .push(RouteAttribute::Source(RouteAddress::Other(ip_octets(
+            route.source,
+        ))));
+    msg.attributes
+        .push(RouteAttribute::Destination(RouteAddress::Other(ip_octets(
+            route.group,
+        ))));
+
+    if let Some(iif) = route.iif {
+        msg.attributes.push(RouteAttribute::Iif(iif));
+    }
+
+    if !route.oifs.is_empty() {
+        let mut ttls = vec![];
+        for oif in &route.oifs {
+            let vif = vifs
+                .iter()
+                .position(|ifindex| *ifindex == oif.ifindex)
+                .ok_or_else(|| {
+                    Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("interface {} is not a multicast vif", oif.ifindex),
+                    )
+                })?;
+            if ttls.len() <= vif {
+                ttls.resize(vif + 1, 0);
+            }
+            ttls[vif] = oif.ttl;
+        }
+        let nexthops = ttls
+            .into_iter()
+            .map(|ttl| {
+                let mut nh = RouteNextHop::default();
+                nh.hops = ttl;
+                nh
+            })
+            .collect();
+        msg.attributes.push(RouteAttribute::MultiPath(nexthops));
+    }
+
+    Ok(msg)
+}
+
+fn ip_octets(addr: IpAddr) -> Vec<u8> {
+    match addr {
+        IpAddr::V4(addr) => addr.octets().to_vec(),
+        IpAddr::V6(addr) => addr.octets().to_vec(),
+    }
+}
+
+fn octets_to_ip(octets: &[u8]) -> Option<IpAddr> {
+    match octets.len() {
+        4 => Some(Ipv4Addr::from(<[u8; 4]>::try_from(octets).ok()?).into()),
+        16 => Some(Ipv6Addr::from(<[u8; 16]>::try_from(octets).ok()?).into()),
+        _ => None,
+    }
+}
+
+pub(super) fn is_multicast_route(msg: &RouteMessage) -> bool {
+    matches!(
+        msg.header.address_family,
+        AddressFamily::Other(RTNL_FAMILY_IPMR | RTNL_FAMILY_IP6MR)
+    )
+}
+
+impl From<RouteMessage> for MulticastRoute {
+    fn from(msg: RouteMessage) -> Self {
+        let unspecified: IpAddr = match msg.header.address_family {
+            AddressFamily::Other(RTNL_FAMILY_IP6MR) => Ipv6Addr::UNSPECIFIED.into(),
+            _ => Ipv4Addr::UNSPECIFIED.into(),
+        };
+        let mut route = MulticastRoute::new(unspecified, unspecified);
+        route.table = msg.header.table as u32;
+        route.flags = msg.header.flags.as_slice().into();
+
+        for attr in msg.attributes {
+            match attr {
+                RouteAttribute::Source(RouteAddress::Other(octets)) => {
+                    route.source = octets_to_ip(&octets).unwrap_or(unspecified);
+                }
+                RouteAttribute::Destination(RouteAddress::Other(octets)) => {
+                    route.group = octets_to_ip(&octets).unwrap_or(unspecified);
+                }
+                RouteAttribute::Iif(iif) => route.iif = Some(iif),
+                RouteAttribute::Table(table) => route.table = table,
+                RouteAttribute::MultiPath(hops) => {
+                    route.oifs = hops
+                        .into_iter()
+                        .map(|nh| MulticastOutput {
+                            ifindex: nh.interface_index,
+                            ttl: nh.hops,
+                        })
+                        .collect();
+                }
+                RouteAttribute::MfcStats(stats) => {
+                    route.stats = Some(MulticastRouteStats {
+                        packets: stats.packets,
+                        bytes: stats.bytes,
+                        wrong_if: stats.wrong_if,
+                    });
+                }
+                RouteAttribute::MulticastExpires(ticks) => {
+                    route.last_use = Some(clock_ticks_to_duration(
+                        ticks.try_into().unwrap_or(u32::MAX),
+                    ));
+                }
+                _ => {}
+            }
+        }
+        route
+    }
+}
+
+/// The interface indexes of the virtual interfaces of `table`, indexed by virtual interface
+/// index. Unused slots are 0.
+///
+/// Only the virtual interfaces of the default table are exposed by the kernel, through procfs.
+pub(super) fn vif_ifindexes(table: u32) -> io::Result<Vec<u32>> {
+    if table != RT_TABLE_DEFAULT {
+        return Err(Error::new(
+            io::ErrorKind::Unsupported,
+            "output interfaces are only supported in the default multicast table",
+        ));
+    }
+    let names = parse_vif_names(&std::fs::read_to_string(IP_MR_VIF)?)?;
+    Ok(names
+        .iter()
+        .map(|name| match name.as_str() {
+            "" => 0,
+            name => ifname_to_index(name),
+        })
+        .collect())
+}
+
+fn parse_vif_names(table: &str) -> io::Result<Vec<String>> {
+    let mut names = vec![];
+    // skip the header line
+    for line in table.lines().skip(1) {
+        let mut fields = line.split_whitespace();
+        let (Some(vif), Some(name)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let vif: usize = vif
+            .parse()
+            .map_err(|_| Error::new(io::ErrorKind::InvalidData, "invalid vif index"))?;
+        if names.len() <= vif {
+            names.resize(vif + 1, String::new());
+        }
+        names[vif] = name.to_string();
+    }
+    Ok(names)
+}
+
+/// The index of the interface with the given name, or 0 if there is none.
+fn ifname_to_index(name: &str) -> u32 {
+    std::fs::read_to_string(format!("/sys/class/net/{name}/ifindex"))
+        .ok()
+        .and_then(|index| index.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use netlink_packet_route::route::{RouteAttribute, RouteFlag, RouteMessage};
+
+    use super::{multicast_route_message, parse_vif_names};
+    use crate::{MulticastOutput, MulticastRoute, RouteFlags};
+
+    #[test]
+    fn it_parses_vif_table() {
+        let table = "Interface      BytesIn  PktsIn  BytesOut PktsOut Flags Local    Remote\n \
+                     0 eth0             0       0         0       0 00000 0100A8C0 00000000\n \
+                     2 pimreg           0       0         0       0 00004 00000000 00000000\n";
+        assert_eq!(
+            parse_vif_names(table).unwrap(),
+            vec!["eth0".to_string(), String::new(), "pimreg".to_string()]
+        );
+    }
+
+    #[test]
+    fn it_places_ttls_by_vif() {
+        let route = MulticastRoute::new(
+            "192.168.1.10".parse().unwrap(),
+            "239.1.1.1".parse().unwrap(),
+        )
+        .with_iif(2)
+        .with_oif(MulticastOutput::new(5).with_ttl(3));
+
+        let msg = multicast_route_message(&route, &[2, 0, 5]).unwrap();
+        let hops = msg
+            .attributes
+            .iter()
+            .find_map(|attr| match attr {
+                RouteAttribute::MultiPath(hops) => Some(hops.clone()),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(
+            hops.iter().map(|nh| nh.hops).collect::<Vec<_>>(),
+            vec![0, 0, 3]
+        );
+
+        assert!(multicast_route_message(&route, &[2]).is_err());
+    }
+
+    #[test]
+    fn it_reads_multicast_routes() {
+        let route = MulticastRoute::new(
+            "192.168.1.10".parse().unwrap(),
+            "239.1.1.1".parse().unwrap(),
+        )
+        .with_iif(2);
+        let mut msg: RouteMessage = multicast_route_message(&route, &[]).unwrap();
+        // dumps list output interfaces by interface index
+        let mut nh = netlink_packet_route::route::RouteNextHop::default();
+        nh.interface_index = 7;
+        nh.hops = 1;
+        msg.attributes.push(RouteAttribute::MultiPath(vec![nh]));
+        msg.header.flags = vec![RouteFlag::Unresolved];
+
+        let parsed = MulticastRoute::from(msg);
+        assert_eq!(parsed.source, route.source);
+        assert_eq!(parsed.group, route.group);
+        assert_eq!(parsed.iif, Some(2));
+        assert_eq!(parsed.table, 253);
+        assert_eq!(parsed.oifs, vec![MulticastOutput::new(7)]);
+        assert!(parsed.flags.contains(RouteFlags::UNRESOLVED));
+    }
+}
@@ -0,0 +1,569 @@
+//! Nexthop objects (`RTM_*NEXTHOP`), which netlink-packet-route has no support for yet.
+//!
+//! The messages are sent on a connection of their own, which also listens for nexthop changes.
+
+use crate::{NextHopChange, NextHopGroupMember, NextHopObject, ResilientGroup, RouteFlags};
+use std::io::{self, Error};
+use std::net::IpAddr;
+
+use async_stream::stream;
+use futures::{channel::mpsc::UnboundedReceiver, Stream, StreamExt};
+use netlink_packet_core::{
+    NetlinkDeserializable, NetlinkHeader, NetlinkMessage, NetlinkPayload, NetlinkSerializable,
+    NLM_F_ACK, NLM_F_CREATE, NLM_F_DUMP, NLM_F_EXCL, NLM_F_REPLACE, NLM_F_REQUEST,
+};
+use netlink_packet_utils::{
+    nla::{DefaultNla, Nla, NlaBuffer, NlasIterator},
+    parsers::{parse_ip, parse_u16, parse_u32},
+    DecodeError, Emitable, Parseable,
+};
+use netlink_proto::ConnectionHandle;
+use netlink_sys::{protocols::NETLINK_ROUTE, AsyncSocket, SocketAddr};
+use tokio::{sync::broadcast, task::JoinHandle};
+
+use super::linux::{clock_ticks_to_duration, duration_to_clock_ticks};
+
+const RTM_NEWNEXTHOP: u16 = 104;
+const RTM_DELNEXTHOP: u16 = 105;
+const RTM_GETNEXTHOP: u16 = 106;
+
+const RTNLGRP_NEXTHOP: u32 = 32;
+
+const AF_UNSPEC: u8 = 0;
+const AF_INET: u8 = 2;
+const AF_INET6: u8 = 10;
+
+const NHA_ID: u16 = 1;
+const NHA_GROUP: u16 = 2;
+const NHA_GROUP_TYPE: u16 = 3;
+const NHA_BLACKHOLE: u16 = 4;
+const NHA_OIF: u16 = 5;
+const NHA_GATEWAY: u16 = 6;
+const NHA_FDB: u16 = 11;
+const NHA_RES_GROUP: u16 = 12;
+
+const NHA_RES_GROUP_BUCKETS: u16 = 1;
+const NHA_RES_GROUP_IDLE_TIMER: u16 = 2;
+const NHA_RES_GROUP_UNBALANCED_TIMER: u16 = 3;
+
+const NEXTHOP_GRP_TYPE_MPATH: u16 = 0;
+const NEXTHOP_GRP_TYPE_RES: u16 = 1;
+
+/// Length of `struct nhmsg`.
+const NEXTHOP_HEADER_LEN: usize = 8;
+/// Length of `struct nexthop_grp`.
+const NEXTHOP_GROUP_ENTRY_LEN: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum NexthopNetlinkMessage {
+    New(NexthopMessage),
+    Del(NexthopMessage),
+    Get(NexthopMessage),
+}
+
+/// `struct nhmsg` followed by its attributes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct NexthopMessage {
+    family: u8,
+    protocol: u8,
+    flags: u32,
+    attributes: Vec<NexthopAttribute>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum NexthopAttribute {
+    Id(u32),
+    Group(Vec<NextHopGroupMember>),
+    GroupType(u16),
+    Blackhole,
+    Oif(u32),
+    Gateway(IpAddr),
+    Fdb,
+    ResGroup(Vec<DefaultNla>),
+    Other(DefaultNla),
+}
+
+impl Nla for NexthopAttribute {
+    fn value_len(&self) -> usize {
+        match self {
+            Self::Id(_) | Self::Oif(_) => 4,
+            Self::GroupType(_) => 2,
+            Self::Group(members) => members.len() * NEXTHOP_GROUP_ENTRY_LEN,
+            Self::Blackhole | Self::Fdb => 0,
+            Self::Gateway(IpAddr::V4(_)) => 4,
+            Self::Gateway(IpAddr::V6(_)) => 16,
+            Self::ResGroup(nlas) => nlas.as_slice().buffer_len(),
+            Self::Other(nla) => nla.value_len(),
+        }
+    }
+
+    fn kind(&self) -> u16 {
+        match self {
+            Self::Id(_) => NHA_ID,
+            Self::Group(_) => NHA_GROUP,
+            Self::GroupType(_) => NHA_GROUP_TYPE,
+            Self::Blackhole => NHA_BLACKHOLE,
+            Self::Oif(_) => NHA_OIF,
+            Self::Gateway(_) => NHA_GATEWAY,
+            Self::Fdb => NHA_FDB,
+            Self::ResGroup(_) => NHA_RES_GROUP,
+            Self::Other(nla) => nla.kind(),
+        }
+    }
+
+    fn is_nested(&self) -> bool {
+        matches!(self, Self::ResGroup(_))
+    }
+
+    fn emit_value(&self, buffer: &mut [u8]) {
+        match self {
+            Self::Id(value) | Self::Oif(value) => buffer.copy_from_slice(&value.to_ne_bytes()),
+            Self::GroupType(value) => buffer.copy_from_slice(&value.to_ne_bytes()),
+            Self::Group(members) => {
+                for (member, entry) in members
+                    .iter()
+                    .zip(buffer.chunks_exact_mut(NEXTHOP_GROUP_ENTRY_LEN))
+                {
+                    entry.fill(0);
+                    entry[..4].copy_from_slice(&member.id.to_ne_bytes());
+                    // the kernel stores the weight minus one
+                    entry[4] = (member.weight.clamp(1, 256) - 1) as u8;
+                }
+            }
+            Self::Blackhole | Self::Fdb => {}
+            Self::Gateway(IpAddr::V4(addr)) => buffer.copy_from_slice(&addr.octets()),
+            Self::Gateway(IpAddr::V6(addr)) => buffer.copy_from_slice(&addr.octets()),
+            Self::ResGroup(nlas) => nlas.as_slice().emit(buffer),
+            Self::Other(nla) => nla.emit_value(buffer),
+        }
+    }
+}
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Parseable<NlaBuffer<&'a T>> for NexthopAttribute {
+    fn parse(buf: &NlaBuffer<&'a T>) -> Result<Self, DecodeError> {
+        let payload = buf.value();
+        Ok(match buf.kind() {
+            NHA_ID => Self::Id(parse_u32(payload)?),
+            NHA_GROUP => Self::Group(
+                payload
+                    .chunks_exact(NEXTHOP_GROUP_ENTRY_LEN)
+                    .map(|entry| NextHopGroupMember {
+                        id: u32::from_ne_bytes([entry[0], entry[1], entry[2], entry[3]]),
+                        weight: entry[4] as u16 + 1,
+                    })
+                    .collect(),
+            ),
+            NHA_GROUP_TYPE => Self::GroupType(parse_u16(payload)?),
+            NHA_BLACKHOLE => Self::Blackhole,
+            NHA_OIF => Self::Oif(parse_u32(payload)?),
+            NHA_GATEWAY => Self::Gateway(parse_ip(payload)?),
+            NHA_FDB => Self::Fdb,
+            NHA_RES_GROUP => Self::ResGroup(
+                NlasIterator::new(payload)
+                    .map(|nla| nla.and_then(|nla| DefaultNla::parse(&nla)))
+                    .collect::<Result<_, _>>()?,
+            ),
+            _ => Self::Other(DefaultNla::parse(buf)?),
+        })
+    }
+}
+
+impl Emitable for NexthopMessage {
+    fn buffer_len(&self) -> usize {
+        NEXTHOP_HEADER_LEN + self.attributes.as_slice().buffer_len()
+    }
+
+    fn emit(&self, buffer: &mut [u8]) {
+        // family, scope, protocol, reserved and flags; the scope must be zero
+        buffer[..NEXTHOP_HEADER_LEN].fill(0);
+        buffer[0] = self.family;
+        buffer[2] = self.protocol;
+        buffer[4..NEXTHOP_HEADER_LEN].copy_from_slice(&self.flags.to_ne_bytes());
+        self.attributes
+            .as_slice()
+            .emit(&mut buffer[NEXTHOP_HEADER_LEN..]);
+    }
+}
+
+impl NexthopMessage {
+    fn parse(buf: &[u8]) -> Result<Self, DecodeError> {
+        if buf.len() < NEXTHOP_HEADER_LEN {
+            return Err(DecodeError::from("nexthop message is too short"));
+        }
+        Ok(Self {
+            family: buf[0],
+            protocol: buf[2],
+            flags: u32::from_ne_bytes([buf[4], buf[5], buf[6], buf[7]]),
+            attributes: NlasIterator::new(&buf[NEXTHOP_HEADER_LEN..])
+                .map(|nla| nla.and_then(|nla| NexthopAttribute::parse(&nla)))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+impl NetlinkSerializable for NexthopNetlinkMessage {
+    fn message_type(&self) -> u16 {
+        match self {
+            Self::New(_) => RTM_NEWNEXTHOP,
+            Self::Del(_) => RTM_DELNEXTHOP,
+            Self::Get(_) => RTM_GETNEXTHOP,
+        }
+    }
+
+    fn buffer_len(&self) -> usize {
+        match self {
+            Self::New(msg) | Self::Del(msg) | Self::Get(msg) => msg.buffer_len(),
+        }
+    }
+
+    fn serialize(&self, buffer: &mut [u8]) {
+        match self {
+            Self::New(msg) | Self::Del(msg) | Self::Get(msg) => msg.emit(buffer),
+        }
+    }
+}
+
+impl NetlinkDeserializable for NexthopNetlinkMessage {
+    type Error = DecodeError;
+
+    fn deserialize(header: &NetlinkHeader, payload: &[u8]) -> Result<Self, Self::Error> {
+        let msg = NexthopMessage::parse(payload)?;
+        match header.message_type {
+            RTM_NEWNEXTHOP => Ok(Self::New(msg)),
+            RTM_DELNEXTHOP => Ok(Self::Del(msg)),
+            RTM_GETNEXTHOP => Ok(Self::Get(msg)),
+            kind => Err(DecodeError::from(format!(
+                "unknown nexthop message type {kind}"
+            ))),
+        }
+    }
+}
+
+impl From<&NextHopObject> for NexthopMessage {
+    fn from(nexthop: &NextHopObject) -> Self {
+        let mut msg = NexthopMessage {
+            // groups take the family of their members
+            family: match nexthop.gateway {
+                _ if !nexthop.group.is_empty() => AF_UNSPEC,
+                Some(IpAddr::V4(_)) => AF_INET,
+                Some(IpAddr::V6(_)) => AF_INET6,
+                None if nexthop.ipv6 => AF_INET6,
+                None => AF_INET,
+            },
+            protocol: u8::from(netlink_packet_route::route::RouteProtocol::from(
+                nexthop.protocol,
+            )),
+            // the kernel reports more flags, but only accepts this one
+            flags: nexthop.flags.bits() & RouteFlags::ONLINK.bits(),
+            attributes: vec![],
+        };
+
+        if nexthop.id != 0 {
+            msg.attributes.push(NexthopAttribute::Id(nexthop.id));
+        }
+        if !nexthop.group.is_empty() {
+            msg.attributes
+                .push(NexthopAttribute::Group(nexthop.group.clone()));
+            match nexthop.resilient {
+                Some(resilient) => {
+                    msg.attributes
+                        .push(NexthopAttribute::GroupType(NEXTHOP_GRP_TYPE_RES));
+                    msg.attributes
+                        .push(NexthopAttribute::ResGroup(resilient_to_netlink(&resilient)));
+                }
+                None => msg
+                    .attributes
+                    .push(NexthopAttribute::GroupType(NEXTHOP_GRP_TYPE_MPATH)),
+            }
+        }
+        if nexthop.blackhole {
+            msg.attributes.push(NexthopAttribute::Blackhole);
+        }
+        if let Some(ifindex) = nexthop.ifindex {
+            msg.attributes.push(NexthopAttribute::Oif(ifindex));
+        }
+        if let Some(gateway) = nexthop.gateway {
+            msg.attributes.push(NexthopAttribute::Gateway(gateway));
+        }
+        if nexthop.fdb {
+            msg.attributes.push(NexthopAttribute::Fdb);
+        }
+        msg
+    }
+}
+
+impl From<NexthopMessage> for NextHopObject {
+    fn from(msg: NexthopMessage) -> Self {
+        let mut nexthop = NextHopObject::new(0)
+            .with_protocol(netlink_packet_route::route::RouteProtocol::from(msg.protocol).into())
+            .with_flags(RouteFlags::from_bits(msg.flags));
+        nexthop.ipv6 = msg.family == AF_INET6;
+        let mut group_type = NEXTHOP_GRP_TYPE_MPATH;
+        let mut resilient = None;
+
+        for attr in msg.attributes {
+            match attr {
+                NexthopAttribute::Id(id) => nexthop.id = id,
+                NexthopAttribute::Group(members) => nexthop.group = members,
+                NexthopAttribute::GroupType(kind) => group_type = kind,
+                NexthopAttribute::Blackhole => nexthop.blackhole = true,
+                NexthopAttribute::Oif(ifindex) => nexthop.ifindex = Some(ifindex),
+                NexthopAttribute::Gateway(gateway) => nexthop.gateway = Some(gateway),
+                NexthopAttribute::Fdb => nexthop.fdb = true,
+                NexthopAttribute::ResGroup(nlas) => resilient = Some(resilient_from_netlink(nlas)),
+                NexthopAttribute::Other(_) => {}
+            }
+        }
+        if group_type == NEXTHOP_GRP_TYPE_RES {
+            nexthop.resilient = resilient.or(Some(ResilientGroup::default()));
+        }
+        nexthop
+    }
+}
+
+fn resilient_to_netlink(resilient: &ResilientGroup) -> Vec<DefaultNla> {
+    let mut nlas = vec![];
+    // zero buckets leaves the choice to the kernel
+    if resilient.buckets != 0 {
+        nlas.push(DefaultNla::new(
+            NHA_RES_GROUP_BUCKETS,
+            resilient.buckets.to_ne_bytes().to_vec(),
+        ));
+    }
+    if let Some(timer) = resilient.idle_timer {
+        nlas.push(DefaultNla::new(
+            NHA_RES_GROUP_IDLE_TIMER,
+            duration_to_clock_ticks(timer).to_ne_bytes().to_vec(),
+        ));
+    }
+    if let Some(timer) = resilient.unbalanced_timer {
+        nlas.push(DefaultNla::new(
+            NHA_RES_GROUP_UNBALANCED_TIMER,
+            duration_to_clock_ticks(timer).to_ne_bytes().to_vec(),
+        ));
+    }
+    nlas
+}
+
+fn resilient_from_netlink(nlas: Vec<DefaultNla>) -> ResilientGroup {
+    let mut resilient = ResilientGroup::default();
+    for nla in nlas {
+        let mut value = vec![0; nla.value_len()];
+        nla.emit_value(&mut value);
+        match nla.kind() {
+            NHA_RES_GROUP_BUCKETS => resilient.buckets = parse_u16(&value).unwrap_or_default(),
+            NHA_RES_GROUP_IDLE_TIMER => {
+                resilient.idle_timer = parse_u32(&value).ok().map(clock_ticks_to_duration)
+            }
+            NHA_RES_GROUP_UNBALANCED_TIMER => {
+                // zero, which the kernel reports when unset, disables the timer
+                resilient.unbalanced_timer = parse_u32(&value)
+                    .ok()
+                    .filter(|ticks| *ticks != 0)
+                    .map(clock_ticks_to_duration)
+            }
+            _ => {}
+        }
+    }
+    resilient
+}
+
+pub(super) struct NexthopHandle {
+    handle: ConnectionHandle<NexthopNetlinkMessage>,
+    join_handle: JoinHandle<()>,
+    listen_handle: JoinHandle<()>,
+    tx: broadcast::Sender<NextHopChange>,
+}
+
+impl NexthopHandle {
+    pub(super) fn new() -> io::Result<Self> {
+        let (mut connection, handle, messages) =
+            netlink_proto::new_connection::<NexthopNetlinkMessage>(NETLINK_ROUTE)?;
+
+        // kernels without nexthop objects (before 5.3) don't know the group, nothing will be
+        // sent on it there anyway
+        _ = connection
+            .socket_mut()
+            .socket_mut()
+            .add_membership(RTNLGRP_NEXTHOP);
+        let (tx, _) = broadcast::channel::<NextHopChange>(16);
+
+        let join_handle = tokio::spawn(connection);
+        let listen_handle = tokio::spawn(Self::listen(messages, tx.clone()));
+
+        Ok(Self {
+            handle,
+            join_handle,
+            listen_handle,
+            tx,
+        })
+    }
+
+    pub(super) async fn list(&self) -> io::Result<Vec<NextHopObject>> {
+        let msg = NexthopNetlinkMessage::Get(NexthopMessage::default());
+        let responses = self.request(msg, NLM_F_REQUEST | NLM_F_DUMP).await?;
+        Ok(responses
+            .into_iter()
+            .filter_map(|msg| match msg {
+                NexthopNetlinkMessage::New(msg) => Some(msg.into()),
+                _ => None,
+            })
+            .collect())
+    }
+
+    pub(super) async fn add(&self, nexthop: &NextHopObject) -> io::Result<()> {
+        let msg = NexthopNetlinkMessage::New(nexthop.into());
+        self.request(msg, NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_EXCL)
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn replace(&self, nexthop: &NextHopObject) -> io::Result<()> {
+        let msg = NexthopNetlinkMessage::New(nexthop.into());
+        self.request(
+            msg,
+            NLM_F_REQUEST | NLM_F_ACK | NLM_F_CREATE | NLM_F_REPLACE,
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub(super) async fn delete(&self, id: u32) -> io::Result<()> {
+        let msg = NexthopNetlinkMessage::Del(NexthopMessage {
+            attributes: vec![NexthopAttribute::Id(id)],
+            ..Default::default()
+        });
+        self.request(msg, NLM_F_REQUEST | NLM_F_ACK).await?;
+        Ok(())
+    }
+
+    pub(super) fn listen_stream(&self) -> impl Stream<Item = NextHopChange> {
+        let mut rx = self.tx.subscribe();
+        stream! {
+            loop {
+                match rx.recv().await {
+                    Ok(ev) => yield ev,
+                    Err(e) => match e {
+                        broadcast::error::RecvError::Closed => break,
+                        broadcast::error::RecvError::Lagged(_) => continue,
+                    }
+                }
+            }
+        }
+    }
+
+    async fn request(
+        &self,
+        msg: NexthopNetlinkMessage,
+        flags: u16,
+    ) -> io::Result<Vec<NexthopNetlinkMessage>> {
+        let mut req =
+            NetlinkMessage::new(NetlinkHeader::default(), NetlinkPayload::InnerMessage(msg));
+        req.header.flags = flags;
+
+        let mut responses = self
+            .handle
+            .request(req, SocketAddr::new(0, 0))
+            .map_err(|e| Error::other(e.to_string()))?;
+
+        let mut messages = vec![];
+        while let Some(msg) = responses.next().await {
+            match msg.payload {
+                NetlinkPayload::InnerMessage(msg) => messages.push(msg),
+                NetlinkPayload::Error(e) if e.code.is_some() => return Err(e.to_io()),
+                _ => {}
+            }
+        }
+        Ok(messages)
+    }
+
+    async fn listen(
+        mut messages: UnboundedReceiver<(NetlinkMessage<NexthopNetlinkMessage>, SocketAddr)>,
+        tx: broadcast::Sender<NextHopChange>,
+    ) {
+        while let Some((message, _)) = messages.next().await {
+            if let NetlinkPayload::InnerMessage(msg) = message.payload {
+                match msg {
+                    NexthopNetlinkMessage::New(msg) => _ = tx.send(NextHopChange::Add(msg.into())),
+                    NexthopNetlinkMessage::Del(msg) => {
+                        _ = tx.send(NextHopChange::Delete(msg.into()))
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+impl Drop for NexthopHandle {
+    fn drop(&mut self) {
+        self.join_handle.abort();
+        self.listen_handle.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use netlink_packet_core::NetlinkSerializable;
+
+    use super::{NexthopMessage, NexthopNetlinkMessage};
+    use crate::{NextHopGroupMember, NextHopObject, ResilientGroup, RouteFlags, RouteProtocol};
+
+    fn round_trip(nexthop: &NextHopObject) -> NextHopObject {
+        let msg = NexthopNetlinkMessage::New(nexthop.into());
+        let mut buf = vec![0; msg.buffer_len()];
+        msg.serialize(&mut buf);
+        NexthopMessage::parse(&buf).unwrap().into()
+    }
+
+    #[test]
+    fn it_round_trips_nexthops() {
+        let nexthop = NextHopObject::new(1)
+            .with_gateway("fe80::1".parse().unwrap())
+            .with_ifindex(2)
+            .with_flags(RouteFlags::ONLINK)
+            .with_protocol(RouteProtocol::Bgp);
+        assert_eq!(round_trip(&nexthop), nexthop.with_ipv6());
+
+        let blackhole = NextHopObject::new(2).with_blackhole();
+        assert_eq!(round_trip(&blackhole), blackhole);
+
+        // a listed nexthop on a link which is down
+        let nexthop = NextHopObject::new(3)
+            .with_ifindex(2)
+            .with_flags(RouteFlags::ONLINK | RouteFlags::LINKDOWN | RouteFlags::DEAD);
+        assert_eq!(round_trip(&nexthop).flags, RouteFlags::ONLINK);
+    }
+
+    #[test]
+    fn it_round_trips_nexthop_groups() {
+        let group = NextHopObject::new(10)
+            .with_group_member(NextHopGroupMember::new(1))
+            .with_group_member(NextHopGroupMember::new(2).with_weight(256));
+        assert_eq!(round_trip(&group), group);
+
+        let resilient = group.with_resilient(ResilientGroup {
+            buckets: 64,
+            idle_timer: Some(Duration::from_secs(120)),
+            unbalanced_timer: None,
+        });
+        assert_eq!(round_trip(&resilient), resilient);
+    }
+
+    #[test]
+    fn it_encodes_nexthop_header() {
+        let msg: NexthopMessage = (&NextHopObject::new(7)
+            .with_ifindex(3)
+            .with_ipv6()
+            .with_protocol(RouteProtocol::Static))
+            .into();
+        let mut buf = vec![0; 8 + 8 + 8];
+        NexthopNetlinkMessage::New(msg).serialize(&mut buf);
+        // AF_INET6, scope 0, RTPROT_STATIC
+        assert_eq!(&buf[..4], &[10, 0, 4, 0]);
+        // NHA_ID
+        assert_eq!(&buf[8..16], &[8, 0, 1, 0, 7, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,133 @@
+//! The socket of route connections, which works around netlink-packet-route failing to parse
+//! some attributes the kernel sends.
+
+use std::io;
+use std::task::{ready, Context, Poll};
+
+use bytes::BufMut;
+use netlink_sys::{AsyncSocket, Socket, SocketAddr, TokioSocket};
+
+const NLMSG_HDRLEN: usize = 16;
+/// Size of `struct rtmsg`.
+const RTMSG_LEN: usize = 12;
+const RTM_NEWROUTE: u16 = 24;
+const RTM_DELROUTE: u16 = 25;
+const RTA_METRICS: u16 = 8;
+const NLA_TYPE_MASK: u16 = !(1 << 15 | 1 << 14);
+
+/// RTAX_CC_ALGO, holding the name of a congestion control algorithm.
+const RTAX_CC_ALGO: u16 = 16;
+
+/// The type RTAX_CC_ALGO is renamed to. netlink-packet-route parses RTAX_CC_ALGO as a u32, and
+/// drops the whole message when the name is longer than 3 bytes. Under this type, unknown to it,
+/// the name is kept as raw bytes.
+pub(super) const RTAX_CC_ALGO_NAME: u16 = 0x3fff;
+
+/// A [`TokioSocket`] fixing up the messages it receives before they are parsed.
+pub(super) struct RouteSocket(TokioSocket);
+
+impl AsyncSocket for RouteSocket {
+    fn socket_ref(&self) -> &Socket {
+        self.0.socket_ref()
+    }
+
+    fn socket_mut(&mut self) -> &mut Socket {
+        self.0.socket_mut()
+    }
+
+    fn new(protocol: isize) -> io::Result<Self> {
+        TokioSocket::new(protocol).map(Self)
+    }
+
+    fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        self.0.poll_send(cx, buf)
+    }
+
+    fn poll_send_to(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        addr: &SocketAddr,
+    ) -> Poll<io::Result<usize>> {
+        self.0.poll_send_to(cx, buf, addr)
+    }
+
+    fn poll_recv<B>(&self, cx: &mut Context<'_>, buf: &mut B) -> Poll<io::Result<()>>
+    where
+        B: BufMut,
+    {
+        self.poll_recv_from(cx, buf).map_ok(|_| ())
+    }
+
+    fn poll_recv_from<B>(&self, cx: &mut Context<'_>, buf: &mut B) -> Poll<io::Result<SocketAddr>>
+    where
+        B: BufMut,
+    {
+        let (datagram, addr) = ready!(self.poll_recv_from_full(cx))?;
+        buf.put_slice(&datagram);
+        Poll::Ready(Ok(addr))
+    }
+
+    fn poll_recv_from_full(&self, cx: &mut Context<'_>) -> Poll<io::Result<(Vec<u8>, SocketAddr)>> {
+        let (mut datagram, addr) = ready!(self.0.poll_recv_from_full(cx))?;
+        rename_cc_algo(&mut datagram);
+        Poll::Ready(Ok((datagram, addr)))
+    }
+}
+
+/// Rename the RTAX_CC_ALGO metrics of the route messages in `datagram` to
+/// [`RTAX_CC_ALGO_NAME`].
+pub(super) fn rename_cc_algo(datagram: &mut [u8]) {
+    let mut offset = 0;
+    while let Some(header) = datagram.get(offset..offset + NLMSG_HDRLEN) {
+        let len = u32::from_ne_bytes(header[..4].try_into().unwrap()) as usize;
+        let kind = u16::from_ne_bytes(header[4..6].try_into().unwrap());
+        if len < NLMSG_HDRLEN || offset + len > datagram.len() {
+            return;
+        }
+        if matches!(kind, RTM_NEWROUTE | RTM_DELROUTE) && len >= NLMSG_HDRLEN + RTMSG_LEN {
+            let attributes = &mut datagram[offset + NLMSG_HDRLEN + RTMSG_LEN..offset + len];
+            for_each_attribute(attributes, |kind, metrics| {
+                if kind == RTA_METRICS {
+                    rename_attributes(metrics, RTAX_CC_ALGO, RTAX_CC_ALGO_NAME);
+                }
+            });
+        }
+        offset += align(len);
+    }
+}
+
+/// Call `f` with the type and payload of each attribute in `buf`.
+fn for_each_attribute(buf: &mut [u8], mut f: impl FnMut(u16, &mut [u8])) {
+    let mut offset = 0;
+    while let Some(header) = buf.get(offset..offset + 4) {
+        let len = u16::from_ne_bytes(header[..2].try_into().unwrap()) as usize;
+        let kind = u16::from_ne_bytes(header[2..4].try_into().unwrap()) & NLA_TYPE_MASK;
+        if len < 4 || offset + len > buf.len() {
+            return;
+        }
+        f(kind, &mut buf[offset + 4..offset + len]);
+        offset += align(len);
+    }
+}
+
+/// Change the type of the attributes of type `from` in `buf` to `to`.
+fn rename_attributes(buf: &mut [u8], from: u16, to: u16) {
+    let buf_len = buf.len();
+    let mut offset = 0;
+    while let Some(header) = buf.get_mut(offset..offset + 4) {
+        let len = u16::from_ne_bytes(header[..2].try_into().unwrap()) as usize;
+        let kind = u16::from_ne_bytes(header[2..4].try_into().unwrap());
+        if len < 4 || offset + len > buf_len {
+            return;
+        }
+        if kind & NLA_TYPE_MASK == from {
+            header[2..4].copy_from_slice(&(kind & !NLA_TYPE_MASK | to).to_ne_bytes());
+        }
+        offset += align(len);
+    }
+}
+
+fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
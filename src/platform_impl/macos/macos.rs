@@ -83,7 +83,7 @@ impl Handle {
     }
 
     pub(crate) async fn delete(&self, route: &Route) -> io::Result<()> {
-        add_or_del_route(route.destination, route.mask(), None, None, false).await
+        add_or_del_route(route.destination, route.mask(), None, None, RTM_DELETE).await
     }
 
     pub(crate) async fn add(&self, route: &Route) -> io::Result<()> {
@@ -92,11 +92,29 @@ impl Handle {
             route.mask(),
             route.gateway,
             route.ifindex,
-            true,
+            RTM_ADD,
         )
         .await
     }
 
+    pub(crate) async fn replace(&self, route: &Route) -> io::Result<()> {
+        let res = add_or_del_route(
+            route.destination,
+            route.mask(),
+            route.gateway,
+            route.ifindex,
+            RTM_CHANGE,
+        )
+        .await;
+        match res {
+            // RTM_CHANGE only modifies existing routes, the socket reports ESRCH if there is none
+            Err(e) if e.kind() == ErrorKind::NotFound || e.raw_os_error() == Some(3) => {
+                self.add(route).await
+            }
+            res => res,
+        }
+    }
+
     pub(crate) async fn list(&self) -> io::Result<Vec<Route>> {
         list_routes().await
     }
@@ -421,8 +439,9 @@ async fn add_or_del_route(
     dst_mask: IpAddr,
     gateway: Option<IpAddr>,
     ifindex: Option<u32>,
-    add: bool,
+    rtm_type: u32,
 ) -> io::Result<()> {
+    let add = rtm_type != RTM_DELETE;
     let mut rtm_flags = (RTF_STATIC | RTF_UP) as i32;
     // TODO not sure about this !add
     if gateway.is_some() || !add {
@@ -434,7 +453,7 @@ async fn add_or_del_route(
         rtm_addrs |= RTA_GATEWAY;
     }
 
-    let rtm_type = if add { RTM_ADD } else { RTM_DELETE } as u8;
+    let rtm_type = rtm_type as u8;
 
     let mut rtmsg = m_rtmsg {
         hdr: rt_msghdr {
@@ -9,8 +9,8 @@ use windows_sys::Win32::{
         IpHelper::{
             CancelMibChangeNotify2, CreateIpForwardEntry2, DeleteIpForwardEntry2, FreeMibTable,
             GetIpForwardTable2, InitializeIpForwardEntry, MibAddInstance, MibDeleteInstance,
-            MibParameterNotification, NotifyRouteChange2, MIB_IPFORWARD_ROW2, MIB_IPFORWARD_TABLE2,
-            MIB_NOTIFICATION_TYPE,
+            MibParameterNotification, NotifyRouteChange2, SetIpForwardEntry2, MIB_IPFORWARD_ROW2,
+            MIB_IPFORWARD_TABLE2, MIB_NOTIFICATION_TYPE,
         },
         Ndis::NET_LUID_LH,
     },
@@ -190,6 +190,38 @@ impl Handle {
         }
         Ok(())
     }
+
+    pub(crate) async fn replace(&self, route: &Route) -> io::Result<()> {
+        let row: MIB_IPFORWARD_ROW2 = route.into();
+
+        let err = unsafe { SetIpForwardEntry2(&row) };
+        match err {
+            ERROR_SUCCESS => Ok(()),
+            // ERROR_NOT_FOUND, there is no route with the same gateway to modify
+            1168 => self.replace_gateway(route).await,
+            _ => Err(code_to_error(err, "error setting entry")),
+        }
+    }
+
+    /// Add `route`, then remove the routes it replaces, which only differ in their gateway.
+    /// `SetIpForwardEntry2` can't change the gateway, as it is part of the key of a route.
+    async fn replace_gateway(&self, route: &Route) -> io::Result<()> {
+        let mut replaced = self.list().await?;
+        replaced.retain(|other| {
+            other.destination == route.destination
+                && other.prefix == route.prefix
+                && route
+                    .ifindex
+                    .is_none_or(|ifindex| other.ifindex == Some(ifindex))
+                && other.gateway != route.gateway
+        });
+
+        self.add(route).await?;
+        for other in &replaced {
+            self.delete(other).await?;
+        }
+        Ok(())
+    }
 }
 
 impl Drop for Handle {
@@ -0,0 +1,360 @@
+use std::io;
+#[cfg(target_os = "linux")]
+use std::net::IpAddr;
+
+#[cfg(target_os = "linux")]
+use crate::RouteFlags;
+use crate::{Handle, Route};
+
+/// A change recorded in a [`RouteTransaction`], or the change undoing one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Add(Route),
+    Delete(Route),
+    Replace(Route),
+    /// Add next hops to an existing route, restoring those removed from a multipath route.
+    #[cfg(target_os = "linux")]
+    Append(Route),
+}
+
+/// A set of route changes which is applied in order, and rolled back if one of them fails.
+///
+/// ```no_run
+/// # use net_route::{Handle, Route};
+/// # #[tokio::main]
+/// # async fn main() -> std::io::Result<()> {
+/// let handle = Handle::new()?;
+/// handle
+///     .transaction()
+///     .add(Route::new("10.14.0.0".parse().unwrap(), 24).with_ifindex(9))
+///     .replace(Route::new("0.0.0.0".parse().unwrap(), 0).with_gateway("192.1.2.1".parse().unwrap()))
+///     .delete(Route::new("10.15.0.0".parse().unwrap(), 24))
+///     .apply()
+///     .await
+/// # }
+/// ```
+pub struct RouteTransaction<'a> {
+    handle: &'a Handle,
+    steps: Vec<Step>,
+}
+
+impl<'a> RouteTransaction<'a> {
+    /// Create an empty transaction applied through `handle`.
+    pub fn new(handle: &'a Handle) -> Self {
+        Self {
+            handle,
+            steps: Vec::new(),
+        }
+    }
+
+    /// Add a route, like [`Handle::add`].
+    #[allow(clippy::should_implement_trait)]
+    pub fn add(mut self, route: Route) -> Self {
+        self.steps.push(Step::Add(route));
+        self
+    }
+
+    /// Remove a route, like [`Handle::delete`].
+    pub fn delete(mut self, route: Route) -> Self {
+        self.steps.push(Step::Delete(route));
+        self
+    }
+
+    /// Add or replace a route, like [`Handle::replace`].
+    pub fn replace(mut self, route: Route) -> Self {
+        self.steps.push(Step::Replace(route));
+        self
+    }
+
+    /// Apply the changes in the order they were recorded.
+    ///
+    /// If a change fails, the changes applied before it are undone in reverse order and the error
+    /// of the failing change is returned. Removed and replaced routes are restored as they were
+    /// listed before applying the transaction.
+    pub async fn apply(self) -> io::Result<()> {
+        // the routes the transaction may have to restore, kept up to date with the applied steps
+        let mut state = if self.steps.iter().any(|step| !matches!(step, Step::Add(_))) {
+            self.handle.list().await?
+        } else {
+            Vec::new()
+        };
+
+        let mut undo = Vec::with_capacity(self.steps.len());
+        for step in self.steps {
+            let inverse = inverse(&step, &mut state);
+            if let Err(e) = run(self.handle, &step).await {
+                return match rollback(self.handle, undo).await {
+                    Ok(()) => Err(e),
+                    Err(undo_err) => Err(io::Error::new(
+                        e.kind(),
+                        format!("{e}, rolling back failed: {undo_err}"),
+                    )),
+                };
+            }
+            undo.extend(inverse);
+        }
+        Ok(())
+    }
+}
+
+impl Handle {
+    /// Start recording a [`RouteTransaction`] applied through this handle.
+    pub fn transaction(&self) -> RouteTransaction<'_> {
+        RouteTransaction::new(self)
+    }
+}
+
+async fn run(handle: &Handle, step: &Step) -> io::Result<()> {
+    match step {
+        Step::Add(route) => handle.add(route).await,
+        Step::Delete(route) => handle.delete(route).await,
+        Step::Replace(route) => handle.replace(route).await,
+        #[cfg(target_os = "linux")]
+        Step::Append(route) => handle.append(route).await,
+    }
+}
+
+/// Undo the applied steps, most recent first. Keeps going after a failure, to restore as much as
+/// possible, and returns the first error.
+async fn rollback(handle: &Handle, undo: Vec<Step>) -> io::Result<()> {
+    let mut res = Ok(());
+    for step in undo.into_iter().rev() {
+        if let Err(e) = run(handle, &step).await {
+            res = res.and(Err(e));
+        }
+    }
+    res
+}
+
+/// The step undoing `step`, updating `state` as if `step` was applied. `None` if there is no
+/// known route to restore.
+fn inverse(step: &Step, state: &mut Vec<Route>) -> Option<Step> {
+    match step {
+        Step::Add(route) => {
+            let route = with_default_metric(route);
+            state.push(route.clone());
+            Some(Step::Delete(route))
+        }
+        Step::Delete(route) => {
+            let idx = state.iter().position(|other| route.matches_key(other))?;
+            #[cfg(target_os = "linux")]
+            if let Some(removed) = take_nexthop(route, &mut state[idx]) {
+                return Some(Step::Append(restorable(removed)));
+            }
+            Some(Step::Add(restorable(state.remove(idx))))
+        }
+        Step::Replace(route) => {
+            let route = with_default_metric(route);
+            // the replaced route is the one with the same key, whatever its next hops
+            let mut key = route.clone();
+            key.gateway = None;
+            key.ifindex = None;
+            let previous = state
+                .iter()
+                .position(|other| key.matches_key(other))
+                .map(|idx| state.remove(idx));
+            state.push(route.clone());
+            Some(match previous {
+                Some(previous) => Step::Replace(restorable(previous)),
+                None => Step::Delete(route),
+            })
+        }
+        #[cfg(target_os = "linux")]
+        Step::Append(_) => unreachable!("only used to undo steps"),
+    }
+}
+
+/// `route` with the metric the system gives it when unset. The metric is part of the key of a
+/// route, so that an unset one means the default rather than any when looking it up.
+fn with_default_metric(route: &Route) -> Route {
+    #[allow(unused_mut)]
+    let mut route = route.clone();
+    #[cfg(target_os = "linux")]
+    {
+        route.metric = Some(route.metric.unwrap_or(match route.destination {
+            IpAddr::V4(_) => 0,
+            IpAddr::V6(_) => 1024,
+        }));
+    }
+    route
+}
+
+/// Remove the next hop removing `route` removes from `listed`, if that is only one of the next
+/// hops of `listed`, and return the route made of it.
+///
+/// Removing an ipv6 route by its gateway only removes that next hop of a multipath route, where
+/// ipv4 multipath routes are removed as a whole.
+#[cfg(target_os = "linux")]
+fn take_nexthop(route: &Route, listed: &mut Route) -> Option<Route> {
+    let gateway = route.gateway?;
+    if !route.destination.is_ipv6() || !route.nexthops.is_empty() || listed.nexthops.len() < 2 {
+        return None;
+    }
+    let idx = listed.nexthops.iter().position(|nexthop| {
+        nexthop.gateway == Some(gateway)
+            && route
+                .ifindex
+                .is_none_or(|ifindex| nexthop.ifindex == Some(ifindex))
+    })?;
+    let mut removed = listed.clone();
+    removed.nexthops = vec![listed.nexthops.remove(idx)];
+    Some(removed)
+}
+
+/// `route` as listed, without what the kernel reports but doesn't accept when adding a route.
+#[cfg(target_os = "linux")]
+fn restorable(mut route: Route) -> Route {
+    let settable = |flags: RouteFlags| {
+        RouteFlags::from_bits(flags.bits() & (RouteFlags::ONLINK | RouteFlags::PERVASIVE).bits())
+    };
+    route.flags = settable(route.flags);
+    for nexthop in &mut route.nexthops {
+        nexthop.flags = settable(nexthop.flags);
+    }
+    route.cache_info = None;
+    route
+}
+
+#[cfg(not(target_os = "linux"))]
+fn restorable(route: Route) -> Route {
+    route
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inverse, with_default_metric, Step};
+    use crate::Route;
+    #[cfg(target_os = "linux")]
+    use crate::{NextHop, RouteFlags};
+
+    #[test]
+    fn it_undoes_steps_with_the_listed_routes() {
+        let listed = Route::new("0.0.0.0".parse().unwrap(), 0)
+            .with_gateway("192.168.1.1".parse().unwrap())
+            .with_ifindex(2);
+        let mut state = vec![listed.clone()];
+
+        let added = Route::new("10.0.0.0".parse().unwrap(), 8).with_ifindex(2);
+        assert_eq!(
+            inverse(&Step::Add(added.clone()), &mut state),
+            Some(Step::Delete(with_default_metric(&added)))
+        );
+
+        let default =
+            Route::new("0.0.0.0".parse().unwrap(), 0).with_gateway("192.168.1.2".parse().unwrap());
+        assert_eq!(
+            inverse(&Step::Replace(default.clone()), &mut state),
+            Some(Step::Replace(listed))
+        );
+
+        // the route added earlier in the transaction is the one to restore
+        let removed = Route::new("10.0.0.0".parse().unwrap(), 8);
+        assert_eq!(
+            inverse(&Step::Delete(removed.clone()), &mut state),
+            Some(Step::Add(with_default_metric(&added)))
+        );
+        assert_eq!(inverse(&Step::Delete(removed), &mut state), None);
+        assert_eq!(state, vec![with_default_metric(&default)]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn it_deletes_added_routes_with_the_default_metric() {
+        let mut state = vec![];
+
+        // without a metric, undoing would remove whichever route with the key comes first
+        let added = Route::new("10.0.0.0".parse().unwrap(), 8).with_ifindex(2);
+        assert_eq!(
+            inverse(&Step::Add(added.clone()), &mut state),
+            Some(Step::Delete(added.with_metric(0)))
+        );
+
+        let replaced = Route::new("2001:db8::".parse().unwrap(), 32).with_ifindex(2);
+        assert_eq!(
+            inverse(&Step::Replace(replaced.clone()), &mut state),
+            Some(Step::Delete(replaced.with_metric(1024)))
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn it_restores_the_removed_next_hop_of_multipath_routes() {
+        let nexthop = |gateway: &str| {
+            NextHop::new()
+                .with_gateway(gateway.parse().unwrap())
+                .with_ifindex(2)
+        };
+        let listed = Route::new("2001:db8::".parse().unwrap(), 32)
+            .with_nexthop(nexthop("fe80::2"))
+            .with_nexthop(nexthop("fe80::3").with_flags(RouteFlags::LINKDOWN))
+            .with_metric(1024);
+        let mut state = vec![listed.clone()];
+
+        // the kernel only removes the next hop with the gateway
+        let removed =
+            Route::new("2001:db8::".parse().unwrap(), 32).with_gateway("fe80::3".parse().unwrap());
+        assert_eq!(
+            inverse(&Step::Delete(removed.clone()), &mut state),
+            Some(Step::Append(
+                Route::new("2001:db8::".parse().unwrap(), 32)
+                    .with_nexthop(nexthop("fe80::3"))
+                    .with_metric(1024)
+            ))
+        );
+        assert_eq!(
+            state,
+            vec![Route::new("2001:db8::".parse().unwrap(), 32)
+                .with_nexthop(nexthop("fe80::2"))
+                .with_metric(1024)]
+        );
+        assert_eq!(inverse(&Step::Delete(removed), &mut state), None);
+
+        // without a gateway, the whole route goes
+        let mut state = vec![listed.clone()];
+        let removed = Route::new("2001:db8::".parse().unwrap(), 32);
+        let Some(Step::Add(restored)) = inverse(&Step::Delete(removed), &mut state) else {
+            panic!("the route isn't restored");
+        };
+        assert_eq!(restored.nexthops.len(), 2);
+        assert_eq!(restored.nexthops[1].flags, RouteFlags::empty());
+        assert!(state.is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn it_replaces_routes_with_the_same_metric() {
+        // e.g. a default route from DHCP
+        let listed = Route::new("0.0.0.0".parse().unwrap(), 0)
+            .with_gateway("192.168.1.1".parse().unwrap())
+            .with_metric(100);
+        let mut state = vec![listed.clone()];
+
+        // the kernel adds a second default route with metric 0 instead
+        let default =
+            Route::new("0.0.0.0".parse().unwrap(), 0).with_gateway("192.168.1.2".parse().unwrap());
+        assert_eq!(
+            inverse(&Step::Replace(default.clone()), &mut state),
+            Some(Step::Delete(default.clone().with_metric(0)))
+        );
+        assert_eq!(state, vec![listed.clone(), default.with_metric(0)]);
+
+        let default = Route::new("0.0.0.0".parse().unwrap(), 0)
+            .with_gateway("192.168.1.2".parse().unwrap())
+            .with_metric(100);
+        assert_eq!(
+            inverse(&Step::Replace(default), &mut state),
+            Some(Step::Replace(listed))
+        );
+
+        // ipv6 routes default to metric 1024
+        let listed = Route::new("::".parse().unwrap(), 0)
+            .with_gateway("fe80::1".parse().unwrap())
+            .with_metric(1024);
+        let mut state = vec![listed.clone()];
+        let default = Route::new("::".parse().unwrap(), 0).with_gateway("fe80::2".parse().unwrap());
+        assert_eq!(
+            inverse(&Step::Replace(default), &mut state),
+            Some(Step::Replace(listed))
+        );
+    }
+}